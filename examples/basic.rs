@@ -0,0 +1,128 @@
+//! The demo scene from the `tracer` binary in a minimal window: it renders once when the
+//! window opens and shows the result, with none of the binary's keys, settings or CLI flags.
+//!
+//!     cargo run --release --example basic
+
+use std::{num::NonZeroU32, rc::Rc, sync::Arc};
+
+use softbuffer::{Context, Surface};
+use tracer::{
+    geometry::{Color, Dielectric, Lambertian, Light, Metal, Object, Point, Sphere, World},
+    scene::{RenderOptions, Scene},
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+
+struct App {
+    scene: Scene,
+    window: Option<Rc<Window>>,
+    context: Option<Context<Rc<Window>>>,
+    surface: Option<Surface<Rc<Window>, Rc<Window>>>,
+    frame: Vec<u32>,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let attributes = Window::default_attributes()
+            .with_title("tracer - basic")
+            .with_inner_size(PhysicalSize::new(800, 450))
+            .with_resizable(false);
+        let window = Rc::new(event_loop.create_window(attributes).unwrap());
+        let context = Context::new(window.clone()).unwrap();
+        self.surface = Some(Surface::new(&context, window.clone()).unwrap());
+        self.context = Some(context);
+        self.window = Some(window);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::RedrawRequested => {
+                let size = self.window.as_ref().unwrap().inner_size();
+                let (Some(surface), Some(width), Some(height)) = (
+                    self.surface.as_mut(),
+                    NonZeroU32::new(size.width),
+                    NonZeroU32::new(size.height),
+                ) else {
+                    return;
+                };
+                if self.frame.len() != (size.width * size.height) as usize {
+                    self.frame = vec![0; (size.width * size.height) as usize];
+                    self.scene.render(
+                        &mut self.frame,
+                        &RenderOptions {
+                            width: size.width,
+                            height: size.height,
+                            max_samples: self.scene.max_samples,
+                            depth: self.scene.depth,
+                            ..Default::default()
+                        },
+                    );
+                }
+                surface.resize(width, height).unwrap();
+                let mut buffer = surface.buffer_mut().unwrap();
+                buffer.copy_from_slice(&self.frame);
+                buffer.present().unwrap();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn sphere(center: Point, radius: f32) -> Arc<Sphere> {
+    Arc::new(Sphere {
+        center,
+        radius,
+        velocity: None,
+    })
+}
+
+fn main() {
+    let mut scene = Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: sphere(Point::new(0., -100.5, -1.), 100.),
+            material: Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "center".into(),
+            shape: sphere(Point::new(0., 0.5, -1.2), 0.5),
+            material: Arc::new(Light::new(Color::new(5., 8., 10.))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "left".into(),
+            shape: sphere(Point::new(-1., 0., -1.), 0.5),
+            material: Arc::new(Dielectric::new(1.5)),
+            cast_shadow: true,
+        },
+        Object {
+            name: "bubble".into(),
+            shape: sphere(Point::new(-1., 0., -1.), 0.4),
+            material: Arc::new(Dielectric::new(1. / 1.5)),
+            cast_shadow: true,
+        },
+        Object {
+            name: "right".into(),
+            shape: sphere(Point::new(1., 0., -1.), 0.5),
+            material: Arc::new(Metal::GOLD),
+            cast_shadow: true,
+        },
+    ]));
+    scene.max_samples = 64;
+    let mut app = App {
+        scene,
+        window: None,
+        context: None,
+        surface: None,
+        frame: Vec::new(),
+    };
+    let event_loop = EventLoop::new().unwrap();
+    let _ = event_loop.run_app(&mut app);
+}
@@ -0,0 +1,107 @@
+//! A Cornell box built from triangles, lit only by a small emissive quad under the ceiling.
+//!
+//! Everything outside the box is black, so all light comes from the area light. The red and
+//! green walls bleed colour onto the white surfaces through indirect bounces.
+//!
+//!     cargo run --release --example cornell_box
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{
+        Color, Dielectric, Lambertian, Light, Material, Metal, Object, Point, Sphere, Triangle,
+        World,
+    },
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+/// Two triangles covering the parallelogram `corner`, `corner + u`, `corner + u + v`, `corner + v`.
+fn quad(
+    name: &str,
+    corner: Point,
+    u: Point,
+    v: Point,
+    material: Arc<dyn Material + Sync + Send>,
+) -> [Object; 2] {
+    let triangle = |a, b, c| Object {
+        name: name.into(),
+        shape: Arc::new(Triangle { a, b, c }),
+        material: material.clone(),
+        cast_shadow: true,
+    };
+    [
+        triangle(corner, corner + u, corner + u + v),
+        triangle(corner, corner + u + v, corner + v),
+    ]
+}
+
+fn main() {
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(Light::new(Color::new(15., 15., 15.)));
+    let x = Point::new(2., 0., 0.);
+    let y = Point::new(0., 2., 0.);
+    let z = Point::new(0., 0., 2.);
+    let mut objects = Vec::new();
+    objects.extend(quad(
+        "floor",
+        Point::new(-1., -1., -1.),
+        x,
+        z,
+        white.clone(),
+    ));
+    objects.extend(quad(
+        "ceiling",
+        Point::new(-1., 1., -1.),
+        x,
+        z,
+        white.clone(),
+    ));
+    objects.extend(quad("back", Point::new(-1., -1., -1.), x, y, white));
+    objects.extend(quad("left", Point::new(-1., -1., -1.), y, z, red));
+    objects.extend(quad("right", Point::new(1., -1., -1.), y, z, green));
+    objects.extend(quad(
+        "light",
+        Point::new(-0.25, 0.99, -0.25),
+        Point::new(0.5, 0., 0.),
+        Point::new(0., 0., 0.5),
+        light,
+    ));
+    objects.push(Object {
+        name: "metal".into(),
+        shape: Arc::new(Sphere {
+            center: Point::new(-0.45, -0.6, -0.3),
+            radius: 0.4,
+            velocity: None,
+        }),
+        material: Arc::new(Metal::new_with_roughness(Color::new(0.8, 0.8, 0.8), 0.2)),
+        cast_shadow: true,
+    });
+    objects.push(Object {
+        name: "glass".into(),
+        shape: Arc::new(Sphere {
+            center: Point::new(0.45, -0.6, 0.3),
+            radius: 0.4,
+            velocity: None,
+        }),
+        material: Arc::new(Dielectric::new(1.5)),
+        cast_shadow: true,
+    });
+
+    let mut scene = Scene::new(World::new(objects));
+    scene.camera_position = Point::new(0., 0., 3.4);
+    scene.camera_fov = 1.45;
+    let options = RenderOptions {
+        width: 256,
+        height: 256,
+        max_samples: 32,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("cornell_box.png", options.width, options.height, &rgb) {
+        eprintln!("cornell_box.png: {e}");
+    }
+}
@@ -0,0 +1,104 @@
+//! A material defined outside the crate: a diffuse surface whose colour alternates in a 3D
+//! checkerboard, next to a plain sphere for comparison.
+//!
+//!     cargo run --release --example custom_material
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{
+        Color, CosinePdf, GradientBackground, Hit, Lambertian, Material, Object, Pdf, Point, Ray,
+        ScatterRecord, Sphere, Vector, World,
+    },
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+struct Checker {
+    even: Color,
+    odd: Color,
+    scale: f32,
+}
+
+impl Checker {
+    fn color_at(&self, p: Point) -> Color {
+        let q = p / self.scale;
+        let cell = q.x.floor() + q.y.floor() + q.z.floor();
+        if cell.rem_euclid(2.) == 0. {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+impl Material for Checker {
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let origin = ray.at(rec.t);
+        let pdf = CosinePdf { normal: rec.normal };
+        Some(ScatterRecord {
+            attenuation: self.color_at(origin),
+            scattered: Ray {
+                origin,
+                direction: pdf.generate(),
+                wavelength: ray.wavelength,
+                time: ray.time,
+            },
+            pdf: Some(Box::new(pdf)),
+        })
+    }
+    fn sample_pdf(&self, _wo: Vector, wi: Vector, normal: Vector) -> f32 {
+        CosinePdf { normal }.value(wi)
+    }
+}
+
+fn sphere(center: Point, radius: f32) -> Arc<Sphere> {
+    Arc::new(Sphere {
+        center,
+        radius,
+        velocity: None,
+    })
+}
+
+fn main() {
+    let mut scene = Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: sphere(Point::new(0., -100.5, -1.), 100.),
+            material: Arc::new(Checker {
+                even: Color::new(0.2, 0.3, 0.1),
+                odd: Color::new(0.9, 0.9, 0.9),
+                scale: 0.5,
+            }),
+            cast_shadow: true,
+        },
+        Object {
+            name: "checker".into(),
+            shape: sphere(Point::new(-0.6, 0., -1.5), 0.5),
+            material: Arc::new(Checker {
+                even: Color::new(0.8, 0.1, 0.1),
+                odd: Color::new(0.9, 0.8, 0.2),
+                scale: 0.2,
+            }),
+            cast_shadow: true,
+        },
+        Object {
+            name: "plain".into(),
+            shape: sphere(Point::new(0.6, 0., -1.5), 0.5),
+            material: Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))),
+            cast_shadow: true,
+        },
+    ]));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    let options = RenderOptions {
+        width: 400,
+        height: 225,
+        max_samples: 32,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("custom_material.png", options.width, options.height, &rgb) {
+        eprintln!("custom_material.png: {e}");
+    }
+}
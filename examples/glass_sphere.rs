@@ -0,0 +1,84 @@
+//! Glass under a sky: a solid sphere, a hollow one (a sphere with a smaller, inverted-IOR
+//! sphere inside it), and a dispersive sphere that splits white light into a spectrum.
+//!
+//! `Dielectric::new(1. / 1.5)` models the air pocket: inside the glass, the interface to
+//! the bubble goes from dense to thin.
+//!
+//!     cargo run --release --example glass_sphere
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{
+        Color, Dielectric, GradientBackground, Lambertian, Material, Object, Point, Sphere, World,
+    },
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn ball(
+    name: &str,
+    center: Point,
+    radius: f32,
+    material: Arc<dyn Material + Sync + Send>,
+) -> Object {
+    Object {
+        name: name.into(),
+        shape: Arc::new(Sphere {
+            center,
+            radius,
+            velocity: None,
+        }),
+        material,
+        cast_shadow: true,
+    }
+}
+
+fn main() {
+    let mut scene = Scene::new(World::new(vec![
+        ball(
+            "ground",
+            Point::new(0., -100.5, -1.),
+            100.,
+            Arc::new(Lambertian::new(Color::from_hex(0x8a9a5b))),
+        ),
+        ball(
+            "solid",
+            Point::new(-1.1, 0., -1.2),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        ),
+        ball(
+            "hollow",
+            Point::new(0., 0., -1.2),
+            0.5,
+            Arc::new(Dielectric::new(1.5)),
+        ),
+        ball(
+            "bubble",
+            Point::new(0., 0., -1.2),
+            0.4,
+            Arc::new(Dielectric::new(1. / 1.5)),
+        ),
+        ball(
+            "prism",
+            Point::new(1.1, 0., -1.2),
+            0.5,
+            Arc::new(Dielectric::with_dispersion(1.5, 0.02, 0.)),
+        ),
+    ]));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    scene.camera_position = Point::new(0., 0.3, 1.);
+    scene.camera_fov = 2.1;
+    let options = RenderOptions {
+        width: 600,
+        height: 300,
+        max_samples: 128,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("glass_sphere.png", options.width, options.height, &rgb) {
+        eprintln!("glass_sphere.png: {e}");
+    }
+}
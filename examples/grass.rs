@@ -0,0 +1,70 @@
+//! Ten thousand short curves scattered on a patch of the ground sphere, rendered as one
+//! `Curves` shape.
+//!
+//!     cargo run --release --example grass
+
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tracer::{
+    curve::{Curve, Curves},
+    geometry::{Color, GradientBackground, Lambertian, Object, Point, Sphere, Vector, World},
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let strands: Vec<Curve> = (0..10_000)
+        .map(|_| {
+            let (x, z) = (rng.gen_range(-1.0..1.0), rng.gen_range(-2.5..-0.5));
+            let root = Point::new(x, -0.5, z);
+            let lean = Vector::new(rng.gen_range(-0.08..0.08), 0., rng.gen_range(-0.08..0.08));
+            let height = rng.gen_range(0.15..0.3);
+            let up = Vector::new(0., height / 3., 0.);
+            Curve::new(
+                [
+                    root,
+                    root + up,
+                    root + 2. * up + lean,
+                    root + 3. * up + 2. * lean,
+                ],
+                0.01,
+                0.002,
+            )
+        })
+        .collect();
+    let mut scene = Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0., -100.5, -1.),
+                radius: 100.,
+                velocity: None,
+            }),
+            material: Arc::new(Lambertian::new(Color::new(0.35, 0.25, 0.15))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "grass".into(),
+            shape: Arc::new(Curves::new(&strands, 2)),
+            material: Arc::new(Lambertian::new(Color::new(0.3, 0.6, 0.2))),
+            cast_shadow: true,
+        },
+    ]));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    scene.camera_position = Point::new(0., 0.2, 0.8);
+    scene.camera_direction = Vector::new(0., -0.25, -1.);
+    scene.camera_fov = 1.8;
+    let options = RenderOptions {
+        width: 320,
+        height: 240,
+        max_samples: 8,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("grass.png", options.width, options.height, &rgb) {
+        eprintln!("grass.png: {e}");
+    }
+}
@@ -0,0 +1,62 @@
+//! Renders the demo scene without opening a window and writes it to a PNG.
+//!
+//! The render is seeded, so running it twice produces the same file.
+//!
+//!     cargo run --release --example headless -- out.png
+
+use std::{env, sync::Arc};
+
+use tracer::{
+    geometry::{Color, Dielectric, Lambertian, Light, Metal, Object, Point, Sphere, World},
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn sphere(center: Point, radius: f32) -> Arc<Sphere> {
+    Arc::new(Sphere {
+        center,
+        radius,
+        velocity: None,
+    })
+}
+
+fn main() {
+    let path = env::args().nth(1).unwrap_or("headless.png".into());
+    let scene = Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: sphere(Point::new(0., -100.5, -1.), 100.),
+            material: Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "center".into(),
+            shape: sphere(Point::new(0., 0.5, -1.2), 0.5),
+            material: Arc::new(Light::new(Color::new(5., 8., 10.))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "left".into(),
+            shape: sphere(Point::new(-1., 0., -1.), 0.5),
+            material: Arc::new(Dielectric::new(1.5)),
+            cast_shadow: true,
+        },
+        Object {
+            name: "right".into(),
+            shape: sphere(Point::new(1., 0., -1.), 0.5),
+            material: Arc::new(Metal::GOLD),
+            cast_shadow: true,
+        },
+    ]));
+    let options = RenderOptions {
+        width: 400,
+        height: 225,
+        max_samples: 64,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png(&path, options.width, options.height, &rgb) {
+        eprintln!("{path}: {e}");
+    }
+}
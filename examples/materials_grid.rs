@@ -0,0 +1,62 @@
+//! Two rows of spheres: metal fuzz from 0 to 1 on top, glass IOR from 1.0 to 2.4 below.
+//!
+//!     cargo run --release --example materials_grid
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{
+        Color, Dielectric, GradientBackground, Lambertian, Metal, Object, Point, Sphere, World,
+    },
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn sphere(center: Point, radius: f32) -> Arc<Sphere> {
+    Arc::new(Sphere {
+        center,
+        radius,
+        velocity: None,
+    })
+}
+
+fn main() {
+    let mut objects = vec![Object {
+        name: "ground".into(),
+        shape: sphere(Point::new(0., -100.9, -2.), 100.),
+        material: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        cast_shadow: true,
+    }];
+    for i in 0..5 {
+        let x = -2.2 + 1.1 * i as f32;
+        let fuzz = i as f32 / 4.;
+        objects.push(Object {
+            name: format!("fuzz {fuzz}"),
+            shape: sphere(Point::new(x, 0.55, -2.), 0.45),
+            material: Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), fuzz)),
+            cast_shadow: true,
+        });
+        let ior = 1. + 0.35 * i as f32;
+        objects.push(Object {
+            name: format!("ior {ior}"),
+            shape: sphere(Point::new(x, -0.45, -2.), 0.45),
+            material: Arc::new(Dielectric::new(ior)),
+            cast_shadow: true,
+        });
+    }
+    let mut scene = Scene::new(World::new(objects));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    scene.camera_position = Point::new(0., 0.1, 1.5);
+    scene.camera_fov = 2.2;
+    let options = RenderOptions {
+        width: 480,
+        height: 270,
+        max_samples: 32,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("materials_grid.png", options.width, options.height, &rgb) {
+        eprintln!("materials_grid.png: {e}");
+    }
+}
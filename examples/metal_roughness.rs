@@ -0,0 +1,58 @@
+//! A row of metal spheres going from a perfect mirror to fully rough.
+//!
+//! `Metal::new_with_roughness` maps perceptual roughness to fuzz as `roughness²`, so the
+//! steps look evenly spaced even though the fuzz values are not.
+//!
+//!     cargo run --release --example metal_roughness
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{Color, GradientBackground, Lambertian, Metal, Object, Point, Sphere, World},
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn main() {
+    let mut objects = vec![Object {
+        name: "ground".into(),
+        shape: Arc::new(Sphere {
+            center: Point::new(0., -100.5, -1.),
+            radius: 100.,
+            velocity: None,
+        }),
+        material: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        cast_shadow: true,
+    }];
+    for i in 0..5 {
+        let roughness = i as f32 / 4.;
+        objects.push(Object {
+            name: format!("roughness {roughness}"),
+            shape: Arc::new(Sphere {
+                center: Point::new(-2.2 + 1.1 * i as f32, 0., -2.),
+                radius: 0.5,
+                velocity: None,
+            }),
+            material: Arc::new(Metal::new_with_roughness(
+                Color::new(0.9, 0.9, 0.9),
+                roughness,
+            )),
+            cast_shadow: true,
+        });
+    }
+    let mut scene = Scene::new(World::new(objects));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    scene.camera_position = Point::new(0., 0.4, 1.5);
+    scene.camera_fov = 2.2;
+    let options = RenderOptions {
+        width: 800,
+        height: 300,
+        max_samples: 128,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("metal_roughness.png", options.width, options.height, &rgb) {
+        eprintln!("metal_roughness.png: {e}");
+    }
+}
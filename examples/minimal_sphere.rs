@@ -0,0 +1,36 @@
+//! The smallest useful program: one diffuse sphere under a sky, rendered to a PNG.
+//!
+//!     cargo run --release --example minimal_sphere
+
+use std::sync::Arc;
+
+use tracer::{
+    geometry::{Color, GradientBackground, Lambertian, Object, Point, Sphere, World},
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+fn main() {
+    let mut scene = Scene::new(World::new(vec![Object {
+        name: "sphere".into(),
+        shape: Arc::new(Sphere {
+            center: Point::new(0., 0., -1.),
+            radius: 0.5,
+            velocity: None,
+        }),
+        material: Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2))),
+        cast_shadow: true,
+    }]));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    let options = RenderOptions {
+        width: 320,
+        height: 240,
+        max_samples: 32,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("minimal_sphere.png", options.width, options.height, &rgb) {
+        eprintln!("minimal_sphere.png: {e}");
+    }
+}
@@ -0,0 +1,116 @@
+//! A globe textured from a tiny equirectangular map embedded in the source.
+//!
+//! The map is 32×16 characters, `#` for land and `.` for water, sampled by the latitude and
+//! longitude of the surface normal.
+//!
+//!     cargo run --release --example textured_globe
+
+use std::{f32::consts::PI, sync::Arc};
+
+use tracer::{
+    geometry::{
+        Color, CosinePdf, GradientBackground, Hit, Lambertian, Material, Object, Pdf, Point, Ray,
+        ScatterRecord, Sphere, Vector, World,
+    },
+    image::write_png,
+    scene::{RenderOptions, Scene},
+};
+
+const MAP: [&str; 16] = [
+    "................................",
+    "......######.....##.####........",
+    "..#########......##########.....",
+    "...########....###########..#...",
+    "....######.....##########.......",
+    ".....####......#.#######........",
+    "......###.......#####.###.......",
+    ".......##.......#####....#......",
+    "........####.....####.......#...",
+    "........#####.....##.......###..",
+    ".........###......##......####..",
+    ".........##...............###...",
+    "..........#.....................",
+    "................................",
+    "...######################.......",
+    "################################",
+];
+
+struct Globe {
+    land: Color,
+    water: Color,
+}
+
+impl Globe {
+    fn color_at(&self, normal: Vector) -> Color {
+        let (theta, phi) = normal.cartesian_to_spherical();
+        let u = 0.5 + phi / (2. * PI);
+        let v = theta / PI;
+        let row = MAP[((v * 16.) as usize).min(15)].as_bytes();
+        match row[((u * 32.) as usize).min(31)] {
+            b'#' => self.land,
+            _ => self.water,
+        }
+    }
+}
+
+impl Material for Globe {
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let pdf = CosinePdf { normal: rec.normal };
+        Some(ScatterRecord {
+            attenuation: self.color_at(rec.normal),
+            scattered: Ray {
+                origin: ray.at(rec.t),
+                direction: pdf.generate(),
+                wavelength: ray.wavelength,
+                time: ray.time,
+            },
+            pdf: Some(Box::new(pdf)),
+        })
+    }
+    fn sample_pdf(&self, _wo: Vector, wi: Vector, normal: Vector) -> f32 {
+        CosinePdf { normal }.value(wi)
+    }
+    fn albedo(&self, rec: &Hit) -> Color {
+        self.color_at(rec.normal)
+    }
+}
+
+fn main() {
+    let mut scene = Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0., -100.6, -1.),
+                radius: 100.,
+                velocity: None,
+            }),
+            material: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            cast_shadow: true,
+        },
+        Object {
+            name: "globe".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0., 0., -1.5),
+                radius: 0.6,
+                velocity: None,
+            }),
+            material: Arc::new(Globe {
+                land: Color::from_rgb8(84, 140, 60),
+                water: Color::from_rgb8(30, 70, 150),
+            }),
+            cast_shadow: true,
+        },
+    ]));
+    scene.background = Arc::new(GradientBackground::sky_blue());
+    let options = RenderOptions {
+        width: 320,
+        height: 240,
+        max_samples: 32,
+        seed: Some(0),
+        ..Default::default()
+    };
+    let rgb = scene.render_with_options(&options);
+    if let Err(e) = write_png("textured_globe.png", options.width, options.height, &rgb) {
+        eprintln!("textured_globe.png: {e}");
+    }
+}
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tracer::scene_file::{parse_scene_with_limits, SceneLimits};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let limits = SceneLimits {
+            max_bytes: 1 << 16,
+            max_objects: 1000,
+            ..Default::default()
+        };
+        let _ = parse_scene_with_limits(text, &limits);
+    }
+});
@@ -0,0 +1,13 @@
+use crate::geometry::{Color, Vector};
+
+pub fn point(v: Vector) -> String {
+    format!("({}, {}, {})", v.x, -v.z, v.y)
+}
+
+pub fn color(c: Color) -> String {
+    format!("({}, {}, {}, 1.0)", c.x, c.y, c.z)
+}
+
+pub fn input(name: &str, value: impl std::fmt::Display) -> String {
+    format!("bsdf.inputs[\"{name}\"].default_value = {value}\n")
+}
@@ -0,0 +1,58 @@
+//! Stable 64-bit hashes of scene content, for cache keys and for checking that two renders
+//! used the same scene. The encoding is fixed (FNV-1a over little-endian values) so a hash
+//! means the same thing on every platform and across runs.
+
+use crate::geometry::Vector;
+
+pub struct ContentHasher {
+    state: u64,
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        ContentHasher::new()
+    }
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        ContentHasher {
+            state: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state = (self.state ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    pub fn write_u64(&mut self, v: u64) {
+        self.write_bytes(&v.to_le_bytes());
+    }
+    pub fn write_bool(&mut self, v: bool) {
+        self.write_bytes(&[v as u8]);
+    }
+    /// Writes `-0.0` as `0.0` and every NaN as the same value, so equal-behaving floats hash
+    /// equally.
+    pub fn write_f32(&mut self, v: f32) {
+        let v = if v == 0. {
+            0.
+        } else if v.is_nan() {
+            f32::NAN
+        } else {
+            v
+        };
+        self.write_bytes(&v.to_le_bytes());
+    }
+    pub fn write_vector(&mut self, v: Vector) {
+        self.write_f32(v.x);
+        self.write_f32(v.y);
+        self.write_f32(v.z);
+    }
+    pub fn write_str(&mut self, s: &str) {
+        self.write_u64(s.len() as u64);
+        self.write_bytes(s.as_bytes());
+    }
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
@@ -0,0 +1,122 @@
+//! Coordinate conventions shared by everything that turns pixels into rays and back.
+//!
+//! - World space is Y-up and the default camera looks down −Z, with +X to its right.
+//! - Screen space is in pixels, (0, 0) is the top-left corner and y grows downward.
+//! - NDC spans −1..1 across the image with +x right and +y up, so the flip between screen and
+//!   world happens only in `screen_to_ndc` and `ndc_to_screen`.
+
+use crate::geometry::{Point, Ray, Vector};
+
+/// Camera axes as `(forward, right, up)`.
+pub type CameraBasis = (Vector, Vector, Vector);
+
+pub fn screen_to_ndc(px: f32, py: f32, width: u32, height: u32) -> (f32, f32) {
+    (2. * px / width as f32 - 1., 1. - 2. * py / height as f32)
+}
+
+pub fn ndc_to_screen(x: f32, y: f32, width: u32, height: u32) -> (f32, f32) {
+    ((x + 1.) * width as f32 / 2., (1. - y) * height as f32 / 2.)
+}
+
+/// Distance from the eye to the image plane when the image is two units wide.
+fn focal_length(fov: f32) -> f32 {
+    2. / (fov / 2.).tan()
+}
+
+/// Angle between the top and bottom edges of the image for the camera `fov` at `aspect`.
+///
+/// The stored `fov` sets `focal_length`, which is not the angle across either edge of the image.
+pub fn vertical_fov(fov: f32, aspect: f32) -> f32 {
+    2. * ((fov / 2.).tan() / (2. * aspect)).atan()
+}
+
+/// The camera `fov` that shows `vertical` between the top and bottom edges at `aspect`; the
+/// inverse of `vertical_fov`.
+pub fn fov_from_vertical(vertical: f32, aspect: f32) -> f32 {
+    2. * ((vertical / 2.).tan() * 2. * aspect).atan()
+}
+
+/// Ray from `origin` through NDC `(x, y)` for a camera with the given basis, `fov` and
+/// `aspect` (width / height).
+pub fn ndc_to_camera_ray(
+    x: f32,
+    y: f32,
+    aspect: f32,
+    fov: f32,
+    origin: Point,
+    (forward, right, up): CameraBasis,
+) -> Ray {
+    Ray::new(
+        origin,
+        (x * right + (y / aspect) * up + focal_length(fov) * forward).normalize(),
+    )
+}
+
+/// NDC of the direction `d` from the camera, or `None` if it points behind the camera.
+pub fn camera_to_ndc(
+    d: Vector,
+    aspect: f32,
+    fov: f32,
+    (forward, right, up): CameraBasis,
+) -> Option<(f32, f32)> {
+    let z = d.dot(forward);
+    if z <= 0. {
+        return None;
+    }
+    let l = focal_length(fov);
+    Some((l * d.dot(right) / z, aspect * l * d.dot(up) / z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::World, scene::Scene};
+
+    const BASIS: CameraBasis = (
+        Vector::new(0., 0., -1.),
+        Vector::new(1., 0., 0.),
+        Vector::new(0., 1., 0.),
+    );
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn screen_ndc_and_camera_round_trip() {
+        for (px, py) in [(0., 0.), (12.5, 3.), (40., 30.), (39.5, 29.5)] {
+            let (x, y) = screen_to_ndc(px, py, 40, 30);
+            assert!(close(ndc_to_screen(x, y, 40, 30), (px, py)));
+            let ray = ndc_to_camera_ray(x, y, 4. / 3., 1.2, Point::ZERO, BASIS);
+            let ndc = camera_to_ndc(ray.direction, 4. / 3., 1.2, BASIS).unwrap();
+            assert!(close(ndc, (x, y)), "{ndc:?} vs {:?}", (x, y));
+        }
+        assert_eq!(camera_to_ndc(Vector::new(0., 0., 1.), 1., 1., BASIS), None);
+    }
+
+    #[test]
+    fn vertical_fov_spans_the_image_height() {
+        for aspect in [0.5, 1., 16. / 9.] {
+            let vertical = vertical_fov(1.2, aspect);
+            assert!((fov_from_vertical(vertical, aspect) - 1.2).abs() < 1e-5);
+            let top = ndc_to_camera_ray(0., 1., aspect, 1.2, Point::ZERO, BASIS).direction;
+            let bottom = ndc_to_camera_ray(0., -1., aspect, 1.2, Point::ZERO, BASIS).direction;
+            assert!((top.dot(bottom).acos() - vertical).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn pixel_origin_is_upper_left_with_x_right_and_y_up() {
+        assert_eq!(screen_to_ndc(0., 0., 40, 30), (-1., 1.));
+        assert_eq!(screen_to_ndc(40., 30., 40, 30), (1., -1.));
+        let scene = Scene::new(World::default());
+        let corner = scene.camera_ray(0., 0., 40, 30).direction;
+        assert!(corner.x < 0. && corner.y > 0. && corner.z < 0.);
+        let right = scene.camera_ray(30., 15., 40, 30).direction;
+        assert!(right.x > 0. && right.y.abs() < 1e-6);
+        let below = scene.camera_ray(20., 25., 40, 30).direction;
+        assert!(below.y < 0. && below.x.abs() < 1e-6);
+        let center = scene.camera_ray(20., 15., 40, 30).direction;
+        assert!(close((center.x, center.y), (0., 0.)) && center.z < 0.);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::{
+    content_hash::ContentHasher,
+    geometry::{Aabb, Hit, Hittable, Interval, Point, Ray, Vector},
+    stats::{self, ShapeType},
+};
+
+/// A flat ribbon along a cubic Bézier centreline that always faces the incoming ray, for hair
+/// and grass. The width is interpolated linearly from `width[0]` to `width[1]`.
+#[derive(Clone, Copy, Debug)]
+pub struct Curve {
+    pub points: [Point; 4],
+    pub width: [f32; 2],
+}
+
+fn lerp(t: f32, a: Vector, b: Vector) -> Vector {
+    (1. - t) * a + t * b
+}
+
+fn split_bezier(p: [Vector; 4]) -> ([Vector; 4], [Vector; 4]) {
+    let ab = lerp(0.5, p[0], p[1]);
+    let bc = lerp(0.5, p[1], p[2]);
+    let cd = lerp(0.5, p[2], p[3]);
+    let abc = lerp(0.5, ab, bc);
+    let bcd = lerp(0.5, bc, cd);
+    let mid = lerp(0.5, abc, bcd);
+    ([p[0], ab, abc, mid], [mid, bcd, cd, p[3]])
+}
+
+fn eval_bezier(p: [Vector; 4], u: f32) -> (Vector, Vector) {
+    let a = [
+        lerp(u, p[0], p[1]),
+        lerp(u, p[1], p[2]),
+        lerp(u, p[2], p[3]),
+    ];
+    let b = [lerp(u, a[0], a[1]), lerp(u, a[1], a[2])];
+    (lerp(u, b[0], b[1]), 3. * (b[1] - b[0]))
+}
+
+impl Curve {
+    pub fn new(points: [Point; 4], start_width: f32, end_width: f32) -> Self {
+        Curve {
+            points,
+            width: [start_width, end_width],
+        }
+    }
+    fn width_at(&self, u: f32) -> f32 {
+        (1. - u) * self.width[0] + u * self.width[1]
+    }
+    /// Splits the curve into `n` pieces with tighter bounds, each covering an equal range of
+    /// the parameter.
+    pub fn split(&self, n: usize) -> Vec<Curve> {
+        (0..n)
+            .map(|i| {
+                let (u0, u1) = (i as f32 / n as f32, (i + 1) as f32 / n as f32);
+                // Control points of the sub-curve over [u0, u1], by blossoming.
+                let blossom = |a: f32, b: f32, c: f32| {
+                    let p = self.points;
+                    let q = [
+                        lerp(a, p[0], p[1]),
+                        lerp(a, p[1], p[2]),
+                        lerp(a, p[2], p[3]),
+                    ];
+                    let r = [lerp(b, q[0], q[1]), lerp(b, q[1], q[2])];
+                    lerp(c, r[0], r[1])
+                };
+                Curve {
+                    points: [
+                        blossom(u0, u0, u0),
+                        blossom(u0, u0, u1),
+                        blossom(u0, u1, u1),
+                        blossom(u1, u1, u1),
+                    ],
+                    width: [self.width_at(u0), self.width_at(u1)],
+                }
+            })
+            .collect()
+    }
+    /// Number of halvings needed before each piece is flat to within a twentieth of the width.
+    fn max_depth(&self, cp: [Vector; 4]) -> u32 {
+        let l0 = (0..2)
+            .map(|i| (cp[i] - 2. * cp[i + 1] + cp[i + 2]).length())
+            .fold(0., f32::max);
+        let eps = self.width[0].max(self.width[1]) * 0.05;
+        let r0 = ((std::f32::consts::SQRT_2 * 6. * l0) / (8. * eps)).ln() / 4f32.ln();
+        if r0.is_finite() {
+            (r0.round().max(0.) as u32).min(10)
+        } else {
+            0
+        }
+    }
+    fn recurse(
+        &self,
+        interval: &Interval,
+        cp: [Vector; 4],
+        u: (f32, f32),
+        depth: u32,
+    ) -> Option<(f32, f32)> {
+        let max_width = self.width_at(u.0).max(self.width_at(u.1));
+        let half = 0.5 * max_width;
+        let Aabb { min: lo, max: hi } = cp
+            .iter()
+            .fold(Aabb::EMPTY, |b, &p| b.union(&Aabb::new(p, p)));
+        if lo.x - half > 0. || hi.x + half < 0. || lo.y - half > 0. || hi.y + half < 0. {
+            return None;
+        }
+        if hi.z + half < interval.min || lo.z - half > interval.max {
+            return None;
+        }
+        if depth > 0 {
+            let (left, right) = split_bezier(cp);
+            let mid = 0.5 * (u.0 + u.1);
+            let near = self.recurse(interval, left, (u.0, mid), depth - 1);
+            let t_max = near.map_or(interval.max, |(t, _)| t);
+            let far = self.recurse(
+                &Interval::new(interval.min, t_max),
+                right,
+                (mid, u.1),
+                depth - 1,
+            );
+            return far.or(near);
+        }
+        // The segment is nearly straight: clip it to the slab between the perpendiculars at
+        // its ends, then measure the distance from the ray to the closest point.
+        let edge_start = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        let edge_end = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge_start < 0. || edge_end < 0. {
+            return None;
+        }
+        let segment = cp[3] - cp[0];
+        let length_sq = segment.x * segment.x + segment.y * segment.y;
+        let w = if length_sq > 0. {
+            (-(cp[0].x * segment.x + cp[0].y * segment.y) / length_sq).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let (p, _) = eval_bezier(cp, w);
+        let u = u.0 + w * (u.1 - u.0);
+        let half = 0.5 * self.width_at(u);
+        if p.x * p.x + p.y * p.y > half * half || !interval.surrounds(p.z) {
+            return None;
+        }
+        Some((p.z, u))
+    }
+}
+
+/// Orthonormal `(x, y)` axes perpendicular to `d`.
+fn ray_frame(d: Vector) -> (Vector, Vector) {
+    let helper = if d.x.abs() > 0.9 {
+        Vector::new(0., 1., 0.)
+    } else {
+        Vector::new(1., 0., 0.)
+    };
+    let x = d.cross(helper).normalize();
+    (x, d.cross(x))
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+        stats::record_shape_test(ShapeType::Curve, || {
+            let frame = ray_frame(ray.direction);
+            let to_ray = |p: Point| {
+                let d = p - ray.origin;
+                Vector::new(d.dot(frame.0), d.dot(frame.1), d.dot(ray.direction))
+            };
+            let cp = self.points.map(to_ray);
+            let depth = self.max_depth(cp);
+            let (t, u) = self.recurse(interval, cp, (0., 1.), depth)?;
+            let (_, tangent) = eval_bezier(self.points, u);
+            let tangent = tangent.normalize();
+            let facing = -1. * ray.direction;
+            let normal = (facing - facing.dot(tangent) * tangent).normalize();
+            Some(Hit {
+                t,
+                normal: if normal.near_zero() { facing } else { normal },
+                is_front: true,
+                object_id: 0,
+                depth: 0,
+                outside_ior: 1.,
+            })
+        })
+    }
+    fn bounding_box(&self) -> Aabb {
+        let half = 0.5 * self.width[0].max(self.width[1]);
+        let pad = Vector::new(half, half, half);
+        self.points
+            .iter()
+            .fold(Aabb::EMPTY, |b, &p| b.union(&Aabb::new(p - pad, p + pad)))
+    }
+    fn centroid(&self) -> Point {
+        eval_bezier(self.points, 0.5).0
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("curve");
+        self.points.iter().for_each(|&p| state.write_vector(p));
+        state.write_f32(self.width[0]);
+        state.write_f32(self.width[1]);
+    }
+}
+
+enum Node {
+    Leaf(usize, usize),
+    Branch(Box<(Aabb, Node)>, Box<(Aabb, Node)>),
+}
+
+/// Many curves as one shape, with a bounding volume hierarchy over them so that thousands of
+/// strands cost about as much as a handful.
+pub struct Curves {
+    curves: Vec<Curve>,
+    root: (Aabb, Node),
+}
+
+const LEAF_SIZE: usize = 4;
+
+fn bounds(curves: &[Curve]) -> Aabb {
+    curves
+        .iter()
+        .fold(Aabb::EMPTY, |b, c| b.union(&c.bounding_box()))
+}
+
+fn build(curves: &mut [Curve], offset: usize) -> (Aabb, Node) {
+    let aabb = bounds(curves);
+    if curves.len() <= LEAF_SIZE {
+        return (aabb, Node::Leaf(offset, offset + curves.len()));
+    }
+    let extent = aabb.max - aabb.min;
+    let axis = |p: Point| {
+        if extent.x >= extent.y && extent.x >= extent.z {
+            p.x
+        } else if extent.y >= extent.z {
+            p.y
+        } else {
+            p.z
+        }
+    };
+    curves.sort_unstable_by(|a, b| axis(a.centroid()).total_cmp(&axis(b.centroid())));
+    let mid = curves.len() / 2;
+    let (left, right) = curves.split_at_mut(mid);
+    (
+        aabb,
+        Node::Branch(
+            Box::new(build(left, offset)),
+            Box::new(build(right, offset + mid)),
+        ),
+    )
+}
+
+fn slab_hit(aabb: &Aabb, ray: &Ray, interval: &Interval) -> bool {
+    let (mut t0, mut t1) = (interval.min, interval.max);
+    for (o, d, lo, hi) in [
+        (ray.origin.x, ray.direction.x, aabb.min.x, aabb.max.x),
+        (ray.origin.y, ray.direction.y, aabb.min.y, aabb.max.y),
+        (ray.origin.z, ray.direction.z, aabb.min.z, aabb.max.z),
+    ] {
+        let inv = 1. / d;
+        let (a, b) = ((lo - o) * inv, (hi - o) * inv);
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        t0 = t0.max(a);
+        t1 = t1.min(b);
+        if t1 < t0 {
+            return false;
+        }
+    }
+    true
+}
+
+impl Curves {
+    /// Each curve is split into `segments` pieces first, which keeps the boxes around long or
+    /// bent strands tight.
+    pub fn new(curves: &[Curve], segments: usize) -> Self {
+        let mut curves: Vec<Curve> = curves
+            .iter()
+            .flat_map(|c| c.split(segments.max(1)))
+            .collect();
+        let root = build(&mut curves, 0);
+        Curves { curves, root }
+    }
+    fn hit_node(&self, node: &(Aabb, Node), ray: &Ray, interval: &Interval) -> Option<Hit> {
+        if !slab_hit(&node.0, ray, interval) {
+            return None;
+        }
+        match &node.1 {
+            Node::Leaf(start, end) => {
+                let mut closest: Option<Hit> = None;
+                for curve in &self.curves[*start..*end] {
+                    let t_max = closest.as_ref().map_or(interval.max, |h| h.t);
+                    if let Some(h) = curve.hit(ray, &Interval::new(interval.min, t_max)) {
+                        closest = Some(h);
+                    }
+                }
+                closest
+            }
+            Node::Branch(left, right) => {
+                let near = self.hit_node(left, ray, interval);
+                let t_max = near.as_ref().map_or(interval.max, |h| h.t);
+                self.hit_node(right, ray, &Interval::new(interval.min, t_max))
+                    .or(near)
+            }
+        }
+    }
+}
+
+impl Hittable for Curves {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+        self.hit_node(&self.root, ray, interval)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.root.0
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("curves");
+        self.curves.iter().for_each(|c| c.hash_content(state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Triangle;
+
+    /// The ribbon `curve` presents to rays along `direction`, as `2 * n` triangles.
+    fn tessellate(curve: &Curve, direction: Vector, n: usize) -> Vec<Triangle> {
+        let edges: Vec<(Point, Point)> = (0..=n)
+            .map(|i| {
+                let u = i as f32 / n as f32;
+                let (p, tangent) = eval_bezier(curve.points, u);
+                let side = 0.5 * curve.width_at(u) * tangent.cross(direction).normalize();
+                (p - side, p + side)
+            })
+            .collect();
+        edges
+            .windows(2)
+            .flat_map(|w| {
+                let ((a, b), (c, d)) = (w[0], w[1]);
+                [Triangle { a, b, c: d }, Triangle { a, b: d, c }]
+            })
+            .collect()
+    }
+
+    fn reference_hit(triangles: &[Triangle], ray: &Ray, interval: &Interval) -> Option<f32> {
+        triangles
+            .iter()
+            .filter_map(|t| t.hit(ray, interval).map(|h| h.t))
+            .min_by(f32::total_cmp)
+    }
+
+    fn curves() -> [Curve; 3] {
+        let p = Point::new;
+        [
+            Curve::new(
+                [p(0., 0., 0.), p(0., 1., 0.), p(0., 2., 0.), p(0., 3., 0.)],
+                0.4,
+                0.4,
+            ),
+            Curve::new(
+                [
+                    p(-1., 0., 0.),
+                    p(-0.5, 2., 0.5),
+                    p(0.5, 2., -0.5),
+                    p(1., 0., 0.),
+                ],
+                0.3,
+                0.1,
+            ),
+            Curve::new(
+                [
+                    p(0., 0., 0.),
+                    p(1., 0.5, 1.),
+                    p(1.5, 1.5, 0.5),
+                    p(1., 3., 0.),
+                ],
+                0.2,
+                0.05,
+            ),
+        ]
+    }
+
+    #[test]
+    fn hit_distances_match_a_tessellated_ribbon() {
+        let interval = Interval::new(1e-4, f32::INFINITY);
+        let direction = Vector::new(0.1, -0.05, -1.).normalize();
+        let (mut hits, mut checked) = (0, 0);
+        for curve in curves() {
+            let triangles = tessellate(&curve, direction, 512);
+            for i in 0..40 {
+                // Off the tessellation's edges, where both triangles may miss by rounding.
+                let u = (i as f32 + 0.37) / 40.;
+                let (target, tangent) = eval_bezier(curve.points, u);
+                let side = tangent.cross(direction).normalize();
+                let width = curve.width_at(u);
+                for offset in [-0.8, -0.3, 0., 0.3, 0.8, 1.5] {
+                    let aim = target + 0.5 * offset * width * side;
+                    let ray = Ray::new(aim - 10. * direction, direction);
+                    let expected = reference_hit(&triangles, &ray, &interval);
+                    let actual = curve.hit(&ray, &interval).map(|h| h.t);
+                    // Points right on the ribbon edge may fall either side.
+                    if offset.abs() == 0.8 && expected.is_some() != actual.is_some() {
+                        continue;
+                    }
+                    checked += 1;
+                    match (expected, actual) {
+                        (Some(e), Some(a)) => {
+                            hits += 1;
+                            assert!((e - a).abs() < 0.02, "t {a} vs reference {e}");
+                        }
+                        (None, None) => {}
+                        _ => panic!("{expected:?} vs {actual:?} at offset {offset}"),
+                    }
+                }
+            }
+        }
+        assert!(hits > 300 && checked > 600, "{hits} hits of {checked}");
+    }
+
+    #[test]
+    fn bundled_curves_hit_like_the_nearest_single_curve() {
+        let interval = Interval::new(1e-4, f32::INFINITY);
+        let curves = curves();
+        let bundle = Curves::new(&curves, 3);
+        let (mut hits, mut mismatches) = (0, 0);
+        for i in 0..400 {
+            let (x, y) = ((i % 20) as f32 * 0.15 - 1.5, (i / 20) as f32 * 0.15);
+            let ray = Ray::new(Point::new(x, y, 5.), Vector::new(0., 0., -1.));
+            let nearest = curves
+                .iter()
+                .filter_map(|c| c.hit(&ray, &interval).map(|h| h.t))
+                .min_by(f32::total_cmp);
+            match (nearest, bundle.hit(&ray, &interval).map(|h| h.t)) {
+                (Some(a), Some(b)) => {
+                    hits += 1;
+                    assert!((a - b).abs() < 1e-2, "{a} vs {b}");
+                }
+                (None, None) => {}
+                // Splitting moves the flattening tolerance, so rays grazing an edge may differ.
+                _ => mismatches += 1,
+            }
+        }
+        assert!(
+            hits > 20 && mismatches <= hits / 10,
+            "{hits} hits, {mismatches} mismatches"
+        );
+    }
+}
@@ -1,11 +1,21 @@
 use std::{
+    f32::consts::PI,
     ops::{Add, Div, Mul, Sub},
-    sync::Arc,
+    sync::{Arc, OnceLock},
 };
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
+use crate::{
+    blender,
+    content_hash::ContentHasher,
+    noise::Perlin,
+    rng::with_rng,
+    stats::{self, ShapeType},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Vector {
     pub x: f32,
@@ -91,6 +101,11 @@ impl Vector {
         y: 0.,
         z: 0.,
     };
+    pub const WHITE: Color = Vector {
+        x: 1.,
+        y: 1.,
+        z: 1.,
+    };
     pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Vector { x, y, z }
     }
@@ -120,8 +135,7 @@ impl Vector {
     }
     pub fn random_unit() -> Self {
         loop {
-            let mut rng = rand::thread_rng();
-            let v = Vector::new(rng.gen(), rng.gen(), rng.gen());
+            let v = with_rng(|rng| Vector::new(rng.gen(), rng.gen(), rng.gen()));
             if v.length_square() > 0. && v.length_square() < 1. {
                 return v.normalize();
             }
@@ -139,6 +153,32 @@ impl Vector {
         let r_out_parallel = -(1. - r_out_perp.length_square()).abs().sqrt() * normal;
         r_out_perp + r_out_parallel
     }
+    /// Unit vector at polar angle `theta` from +Y and azimuth `phi` from +X towards +Z.
+    pub fn spherical_to_cartesian(theta: f32, phi: f32) -> Self {
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        Vector::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin())
+    }
+    /// Inverse of `spherical_to_cartesian`: `(theta, phi)` with `theta` in [0, π] and `phi` in
+    /// (-π, π]. The length of `self` is ignored.
+    pub fn cartesian_to_spherical(self) -> (f32, f32) {
+        let v = self.normalize();
+        (v.y.clamp(-1., 1.).acos(), v.z.atan2(v.x))
+    }
+    pub fn clamp(self, lo: f32, hi: f32) -> Self {
+        Vector {
+            x: self.x.clamp(lo, hi),
+            y: self.y.clamp(lo, hi),
+            z: self.z.clamp(lo, hi),
+        }
+    }
+    pub fn clamp01(self) -> Self {
+        self.clamp(0., 1.)
+    }
+    pub fn is_valid(self) -> bool {
+        [self.x, self.y, self.z]
+            .iter()
+            .all(|c| c.is_finite() && *c >= 0.)
+    }
 }
 
 pub type Point = Vector;
@@ -147,15 +187,53 @@ pub type Point = Vector;
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    pub wavelength: Option<f32>,
+    pub time: f32,
 }
 
 impl Ray {
     pub const fn new(origin: Point, direction: Vector) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelength: None,
+            time: 0.,
+        }
     }
     pub fn at(&self, t: f32) -> Point {
         self.origin + t * self.direction
     }
+    pub fn may_hit_sphere(&self, center: Point, radius: f32, interval: &Interval) -> bool {
+        // Distances along the ray are in units of `t`, so scale by the squared direction length.
+        let a = self.direction.length_square();
+        let l = center - self.origin;
+        let tca = l.dot(self.direction) / a;
+        let d2 = l.length_square() - tca * tca * a;
+        let r2 = radius * radius;
+        if d2 > r2 {
+            false
+        } else {
+            let thc = ((r2 - d2) / a).sqrt();
+            tca + thc > interval.min && tca - thc < interval.max
+        }
+    }
+}
+
+pub struct RayDifferential {
+    pub rx_origin: Point,
+    pub rx_direction: Vector,
+    pub ry_origin: Point,
+    pub ry_direction: Vector,
+}
+
+impl RayDifferential {
+    /// Approximate world-space size of the pixel footprint where `ray` travels `t`.
+    pub fn footprint(&self, ray: &Ray, t: f32) -> f32 {
+        let p = ray.at(t);
+        let dx = self.rx_origin + t * self.rx_direction - p;
+        let dy = self.ry_origin + t * self.ry_direction - p;
+        dx.length().max(dy.length())
+    }
 }
 
 #[derive(Debug)]
@@ -172,51 +250,374 @@ impl Interval {
     pub fn surrounds(&self, x: f32) -> bool {
         self.min < x && self.max > x
     }
+    pub fn length(&self) -> f32 {
+        self.max - self.min
+    }
+    pub fn midpoint(&self) -> f32 {
+        0.5 * (self.min + self.max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Aabb {
+        min: Point::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        max: Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+    };
+    pub const fn new(min: Point, max: Point) -> Self {
+        Aabb { min, max }
+    }
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+    pub fn contains_box(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && other.max.x <= self.max.x
+            && other.max.y <= self.max.y
+            && other.max.z <= self.max.z
+    }
+    pub fn corners(&self) -> [Point; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Point::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
 }
 
 pub struct Hit {
     pub t: f32,
     pub normal: Vector,
     pub is_front: bool,
+    /// Index of the hit object in `World::objects` order; shapes leave it 0 and the world fills it in.
+    pub object_id: usize,
+    /// Number of bounces before this hit, filled in by `Scene::trace`.
+    pub depth: u32,
+    /// Refraction index of the medium on the outside of the surface, filled in by `Scene::trace`.
+    pub outside_ior: f32,
+}
+
+/// Everything known about a surface point, for callers outside the bounce loop such as
+/// denoising passes and debugging tools. See `Scene::intersect`.
+pub struct Intersection {
+    pub t: f32,
+    pub point: Point,
+    /// Outward surface normal, whichever side the ray came from.
+    pub geometric_normal: Vector,
+    /// Normal used for shading, facing the incoming ray.
+    pub shading_normal: Vector,
+    pub tangent: Vector,
+    pub bitangent: Vector,
+    pub uv: (f32, f32),
+    pub object_id: usize,
+    pub material: Arc<dyn Material + Sync + Send>,
 }
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit>;
+    fn bounding_box(&self) -> Aabb;
+    fn bounding_sphere(&self) -> (Point, f32) {
+        let b = self.bounding_box();
+        (0.5 * (b.min + b.max), 0.5 * (b.max - b.min).length())
+    }
+    /// Representative point for partitioning primitives. Unlike the bounding-box centre it
+    /// does not shift with padding or orientation, which matters for thin shapes.
+    fn centroid(&self) -> Point {
+        let b = self.bounding_box();
+        0.5 * (b.min + b.max)
+    }
+    fn contains(&self, _p: Point) -> bool {
+        false
+    }
+    /// Surface parameterisation at `p`, a point on the shape.
+    fn uv(&self, _p: Point) -> (f32, f32) {
+        (0., 0.)
+    }
+    /// How far the surface point `p` moves over one shutter interval.
+    fn velocity_at(&self, _p: Point) -> Vector {
+        Vector::ZERO
+    }
+    fn blender_python(&self) -> Option<String> {
+        None
+    }
+    /// Feeds everything that affects how the shape renders into `state`. Shapes that don't
+    /// override this hash only a generic tag.
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("shape");
+    }
 }
 
 pub struct Sphere {
     pub center: Point,
     pub radius: f32,
+    pub velocity: Option<Vector>,
+}
+
+impl Sphere {
+    pub fn center_at(&self, time: f32) -> Point {
+        self.center + time * self.velocity.unwrap_or(Vector::ZERO)
+    }
+    pub fn translate(&self, offset: Vector) -> Sphere {
+        self.with_center(self.center + offset)
+    }
+    pub fn scale(&self, factor: f32) -> Sphere {
+        Sphere {
+            center: self.center,
+            radius: self.radius * factor,
+            velocity: self.velocity,
+        }
+    }
+    pub fn with_center(&self, center: Point) -> Sphere {
+        Sphere {
+            center,
+            radius: self.radius,
+            velocity: self.velocity,
+        }
+    }
+    pub fn tessellate(&self, subdivisions: u32) -> Vec<Triangle> {
+        let t = (1. + 5f32.sqrt()) / 2.;
+        let vertices = [
+            Vector::new(-1., t, 0.),
+            Vector::new(1., t, 0.),
+            Vector::new(-1., -t, 0.),
+            Vector::new(1., -t, 0.),
+            Vector::new(0., -1., t),
+            Vector::new(0., 1., t),
+            Vector::new(0., -1., -t),
+            Vector::new(0., 1., -t),
+            Vector::new(t, 0., -1.),
+            Vector::new(t, 0., 1.),
+            Vector::new(-t, 0., -1.),
+            Vector::new(-t, 0., 1.),
+        ]
+        .map(Vector::normalize);
+        const FACES: [[usize; 3]; 20] = [
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+        let mut faces: Vec<[Vector; 3]> = FACES
+            .iter()
+            .map(|f| [vertices[f[0]], vertices[f[1]], vertices[f[2]]])
+            .collect();
+        for _ in 0..subdivisions {
+            faces = faces
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let ab = (0.5 * (a + b)).normalize();
+                    let bc = (0.5 * (b + c)).normalize();
+                    let ca = (0.5 * (c + a)).normalize();
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        faces
+            .into_iter()
+            .map(|[a, b, c]| Triangle {
+                a: self.center + self.radius * a,
+                b: self.center + self.radius * b,
+                c: self.center + self.radius * c,
+            })
+            .collect()
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
-        let l = self.center - ray.origin;
-        let tca = l.dot(ray.direction);
-        let d2 = l.length_square() - tca * tca;
-        let r2 = self.radius * self.radius;
-        if d2 > r2 {
-            None
-        } else {
-            let thc = (r2 - d2).sqrt();
-            let t0 = tca - thc;
-            let t1 = tca + thc;
-            if interval.surrounds(t0) {
-                Some(Hit {
-                    t: t0,
-                    normal: (ray.at(t0) - self.center).normalize(),
-                    is_front: true,
-                })
-            } else if interval.surrounds(t1) {
-                Some(Hit {
-                    t: t1,
-                    normal: (self.center - ray.at(t1)).normalize(),
-                    is_front: false,
-                })
-            } else {
+        stats::record_shape_test(ShapeType::Sphere, || {
+            let center = self.center_at(ray.time);
+            let l = center - ray.origin;
+            let tca = l.dot(ray.direction);
+            let d2 = l.length_square() - tca * tca;
+            let r2 = self.radius * self.radius;
+            if d2 > r2 {
                 None
+            } else {
+                let thc = (r2 - d2).sqrt();
+                let t0 = tca - thc;
+                let t1 = tca + thc;
+                if interval.surrounds(t0) {
+                    Some(Hit {
+                        t: t0,
+                        normal: (ray.at(t0) - center).normalize(),
+                        is_front: true,
+                        object_id: 0,
+                        depth: 0,
+                        outside_ior: 1.,
+                    })
+                } else if interval.surrounds(t1) {
+                    Some(Hit {
+                        t: t1,
+                        normal: (center - ray.at(t1)).normalize(),
+                        is_front: false,
+                        object_id: 0,
+                        depth: 0,
+                        outside_ior: 1.,
+                    })
+                } else {
+                    None
+                }
+            }
+        })
+    }
+    fn bounding_box(&self) -> Aabb {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        let end = self.center_at(1.);
+        Aabb::new(self.center - r, self.center + r).union(&Aabb::new(end - r, end + r))
+    }
+    fn bounding_sphere(&self) -> (Point, f32) {
+        let sweep = 0.5 * self.velocity.unwrap_or(Vector::ZERO);
+        (self.center + sweep, self.radius + sweep.length())
+    }
+    fn centroid(&self) -> Point {
+        self.center
+    }
+    fn contains(&self, p: Point) -> bool {
+        (p - self.center).length_square() < self.radius * self.radius
+    }
+    fn blender_python(&self) -> Option<String> {
+        Some(format!(
+            concat!(
+                "bpy.ops.mesh.primitive_uv_sphere_add(radius={}, location={}, segments=64, ring_count=32)\n",
+                "bpy.ops.object.shade_smooth()\n",
+                "obj = bpy.context.active_object\n"
+            ),
+            self.radius,
+            blender::point(self.center)
+        ))
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("sphere");
+        state.write_vector(self.center);
+        state.write_f32(self.radius);
+        state.write_vector(self.velocity.unwrap_or(Vector::ZERO));
+    }
+    fn uv(&self, p: Point) -> (f32, f32) {
+        let (theta, phi) = (p - self.center).cartesian_to_spherical();
+        (0.5 + phi / (2. * PI), theta / PI)
+    }
+    fn velocity_at(&self, _p: Point) -> Vector {
+        self.velocity.unwrap_or(Vector::ZERO)
+    }
+}
+
+pub struct Triangle {
+    pub a: Point,
+    pub b: Point,
+    pub c: Point,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+        stats::record_shape_test(ShapeType::Triangle, || {
+            let e1 = self.b - self.a;
+            let e2 = self.c - self.a;
+            let p = ray.direction.cross(e2);
+            let det = e1.dot(p);
+            if det.abs() < 1e-8 {
+                return None;
+            }
+            let inv_det = 1. / det;
+            let s = ray.origin - self.a;
+            let u = s.dot(p) * inv_det;
+            if !(0. ..=1.).contains(&u) {
+                return None;
+            }
+            let q = s.cross(e1);
+            let v = ray.direction.dot(q) * inv_det;
+            if v < 0. || u + v > 1. {
+                return None;
             }
+            let t = e2.dot(q) * inv_det;
+            if !interval.surrounds(t) {
+                return None;
+            }
+            let normal = e1.cross(e2).normalize();
+            let is_front = ray.direction.dot(normal) < 0.;
+            Some(Hit {
+                t,
+                normal: if is_front { normal } else { -1. * normal },
+                is_front,
+                object_id: 0,
+                depth: 0,
+                outside_ior: 1.,
+            })
+        })
+    }
+    fn bounding_box(&self) -> Aabb {
+        let pad = Vector::new(1e-4, 1e-4, 1e-4);
+        Aabb::new(self.a - pad, self.a + pad)
+            .union(&Aabb::new(self.b - pad, self.b + pad))
+            .union(&Aabb::new(self.c - pad, self.c + pad))
+    }
+    fn centroid(&self) -> Point {
+        (self.a + self.b + self.c) / 3.
+    }
+    fn blender_python(&self) -> Option<String> {
+        Some(format!(
+            concat!(
+                "mesh = bpy.data.meshes.new(\"triangle\")\n",
+                "mesh.from_pydata([{}, {}, {}], [], [(0, 1, 2)])\n",
+                "obj = bpy.data.objects.new(\"triangle\", mesh)\n",
+                "bpy.context.collection.objects.link(obj)\n"
+            ),
+            blender::point(self.a),
+            blender::point(self.b),
+            blender::point(self.c)
+        ))
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("triangle");
+        state.write_vector(self.a);
+        state.write_vector(self.b);
+        state.write_vector(self.c);
+    }
+    /// Barycentric weights of `b` and `c`.
+    fn uv(&self, p: Point) -> (f32, f32) {
+        let (e1, e2, d) = (self.b - self.a, self.c - self.a, p - self.a);
+        let n = e1.cross(e2);
+        let area = n.length_square();
+        if area == 0. {
+            return (0., 0.);
         }
+        (d.cross(e2).dot(n) / area, e1.cross(d).dot(n) / area)
     }
 }
 
@@ -229,18 +630,290 @@ fn color(r: f32, g: f32, b: f32) -> u32 {
 
 pub type Color = Vector;
 
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Color {
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::new(
+            srgb_to_linear(r as f32 / 255.),
+            srgb_to_linear(g as f32 / 255.),
+            srgb_to_linear(b as f32 / 255.),
+        )
+    }
+    /// Parses `0xRRGGBB` as sRGB and returns it in linear space.
+    pub fn from_hex(hex: u32) -> Color {
+        Color::from_rgb8((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+    }
+}
+
 pub fn gamma(c: Color) -> u32 {
+    let c = c.clamp01();
     color(c.x.sqrt(), c.y.sqrt(), c.z.sqrt())
 }
 
-pub enum OnHit {
-    None,
-    Scatter { attenuation: Color, scattered: Ray },
-    Emitted { color: Color },
+pub fn heat(t: f32) -> u32 {
+    let t = t.clamp(0., 1.);
+    color(
+        (2. * t - 1.).clamp(0., 1.),
+        1. - (2. * t - 1.).abs(),
+        (1. - 2. * t).clamp(0., 1.),
+    )
+}
+
+pub fn gamma_rgb8(c: Color) -> [u8; 3] {
+    let c = c.clamp01();
+    [c.x.sqrt(), c.y.sqrt(), c.z.sqrt()].map(|v| (v * 255.) as u8)
+}
+
+/// Pixel layouts `hdr_to_u32` can pack into; deeper HDR formats will join `Rgb8` here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    /// 8 bits per channel as `0x00RRGGBB`, the layout softbuffer presents.
+    Rgb8,
+}
+
+/// Applies `exposure`, then a `1 / gamma` curve, and packs the result for `format`. A gamma that
+/// is not positive leaves the values linear.
+pub fn hdr_to_u32(c: Color, exposure: f32, gamma: f32, format: SurfaceFormat) -> u32 {
+    let c = (exposure * c).clamp01();
+    let inv_gamma = if gamma > 0. { 1. / gamma } else { 1. };
+    match format {
+        SurfaceFormat::Rgb8 => color(
+            c.x.powf(inv_gamma),
+            c.y.powf(inv_gamma),
+            c.z.powf(inv_gamma),
+        ),
+    }
+}
+
+pub const VISIBLE_MIN: f32 = 380.;
+pub const VISIBLE_MAX: f32 = 700.;
+
+/// CIE 1931 2° colour matching functions from 380 nm to 780 nm in 5 nm steps.
+const CIE_1931: [[f32; 3]; 81] = [
+    [0.001368, 0.000039, 0.00645],
+    [0.002236, 0.000064, 0.01055],
+    [0.004243, 0.00012, 0.02005],
+    [0.00765, 0.000217, 0.03621],
+    [0.01431, 0.000396, 0.06785],
+    [0.02319, 0.00064, 0.1102],
+    [0.04351, 0.00121, 0.2074],
+    [0.07763, 0.00218, 0.3713],
+    [0.13438, 0.004, 0.6456],
+    [0.21477, 0.0073, 1.03905],
+    [0.2839, 0.0116, 1.3856],
+    [0.3285, 0.01684, 1.62296],
+    [0.34828, 0.023, 1.74706],
+    [0.34806, 0.0298, 1.7826],
+    [0.3362, 0.038, 1.77211],
+    [0.3187, 0.048, 1.7441],
+    [0.2908, 0.06, 1.6692],
+    [0.2511, 0.0739, 1.5281],
+    [0.19536, 0.09098, 1.28764],
+    [0.1421, 0.1126, 1.0419],
+    [0.09564, 0.13902, 0.81295],
+    [0.05795, 0.1693, 0.6162],
+    [0.03201, 0.20802, 0.46518],
+    [0.0147, 0.2586, 0.3533],
+    [0.0049, 0.323, 0.272],
+    [0.0024, 0.4073, 0.2123],
+    [0.0093, 0.503, 0.1582],
+    [0.0291, 0.6082, 0.1117],
+    [0.06327, 0.71, 0.07825],
+    [0.1096, 0.7932, 0.05725],
+    [0.1655, 0.862, 0.04216],
+    [0.22575, 0.91485, 0.02984],
+    [0.2904, 0.954, 0.0203],
+    [0.3597, 0.9803, 0.0134],
+    [0.43345, 0.99495, 0.00875],
+    [0.51205, 1.0, 0.00575],
+    [0.5945, 0.995, 0.0039],
+    [0.6784, 0.9786, 0.00275],
+    [0.7621, 0.952, 0.0021],
+    [0.8425, 0.9154, 0.0018],
+    [0.9163, 0.87, 0.00165],
+    [0.9786, 0.8163, 0.0014],
+    [1.0263, 0.757, 0.0011],
+    [1.0567, 0.6949, 0.001],
+    [1.0622, 0.631, 0.0008],
+    [1.0456, 0.5668, 0.0006],
+    [1.0026, 0.503, 0.00034],
+    [0.9384, 0.4412, 0.00024],
+    [0.85445, 0.381, 0.00019],
+    [0.7514, 0.321, 0.0001],
+    [0.6424, 0.265, 0.00005],
+    [0.5419, 0.217, 0.00003],
+    [0.4479, 0.175, 0.00002],
+    [0.3608, 0.1382, 0.00001],
+    [0.2835, 0.107, 0.0],
+    [0.2187, 0.0816, 0.0],
+    [0.1649, 0.061, 0.0],
+    [0.1212, 0.04458, 0.0],
+    [0.0874, 0.032, 0.0],
+    [0.0636, 0.0232, 0.0],
+    [0.04677, 0.017, 0.0],
+    [0.0329, 0.01192, 0.0],
+    [0.0227, 0.00821, 0.0],
+    [0.01584, 0.005723, 0.0],
+    [0.011359, 0.004102, 0.0],
+    [0.008111, 0.002929, 0.0],
+    [0.00579, 0.002091, 0.0],
+    [0.004109, 0.001484, 0.0],
+    [0.002899, 0.001047, 0.0],
+    [0.002049, 0.00074, 0.0],
+    [0.00144, 0.00052, 0.0],
+    [0.001, 0.000361, 0.0],
+    [0.00069, 0.000249, 0.0],
+    [0.000476, 0.000172, 0.0],
+    [0.000332, 0.00012, 0.0],
+    [0.000235, 0.000085, 0.0],
+    [0.000166, 0.00006, 0.0],
+    [0.000117, 0.000042, 0.0],
+    [0.000083, 0.00003, 0.0],
+    [0.000059, 0.000021, 0.0],
+    [0.000042, 0.000015, 0.0],
+];
+
+/// CIE 1931 colour matching functions at `wavelength` nm, interpolated linearly between table
+/// entries and zero outside 380..780 nm.
+pub fn cie_xyz(wavelength: f32) -> Vector {
+    let x = (wavelength - 380.) / 5.;
+    if !(0. ..=(CIE_1931.len() - 1) as f32).contains(&x) {
+        return Vector::ZERO;
+    }
+    let i = (x as usize).min(CIE_1931.len() - 2);
+    let t = x - i as f32;
+    let [a, b] = [CIE_1931[i], CIE_1931[i + 1]].map(|[x, y, z]| Vector::new(x, y, z));
+    (1. - t) * a + t * b
+}
+
+pub fn xyz_to_rgb(xyz: Vector) -> Color {
+    Color::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+fn wavelength_weight(wavelength: f32) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| {
+        let steps = (VISIBLE_MAX - VISIBLE_MIN) as u32;
+        let sum = (0..steps).fold(Color::ZERO, |sum, i| {
+            sum + xyz_to_rgb(cie_xyz(VISIBLE_MIN + i as f32 + 0.5))
+        });
+        sum / steps as f32
+    });
+    let rgb = xyz_to_rgb(cie_xyz(wavelength));
+    Color::new(rgb.x / white.x, rgb.y / white.y, rgb.z / white.z)
+}
+
+/// MIS weight for a sample drawn from a pdf of value `f`, combined with a strategy of value `g`.
+pub fn balance_heuristic(f: f32, g: f32) -> f32 {
+    if f + g > 0. {
+        f / (f + g)
+    } else {
+        0.
+    }
+}
+
+/// Like `balance_heuristic` but with squared pdfs, which favours the stronger strategy more.
+pub fn power_heuristic(f: f32, g: f32) -> f32 {
+    balance_heuristic(f * f, g * g)
+}
+
+pub trait Pdf {
+    fn value(&self, direction: Vector) -> f32;
+    fn generate(&self) -> Vector;
+}
+
+pub struct CosinePdf {
+    pub normal: Vector,
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vector) -> f32 {
+        direction.normalize().dot(self.normal).max(0.) / PI
+    }
+    fn generate(&self) -> Vector {
+        let direction = self.normal + Vector::random_unit();
+        if direction.near_zero() {
+            self.normal
+        } else {
+            direction.normalize()
+        }
+    }
+}
+
+/// Cosine lobes on both sides of the surface, the back one weighted by `transmittance`.
+pub struct TranslucentPdf {
+    pub normal: Vector,
+    pub transmittance: f32,
+}
+
+impl Pdf for TranslucentPdf {
+    fn value(&self, direction: Vector) -> f32 {
+        let cos = direction.normalize().dot(self.normal);
+        if cos >= 0. {
+            (1. - self.transmittance) * cos / PI
+        } else {
+            self.transmittance * -cos / PI
+        }
+    }
+    fn generate(&self) -> Vector {
+        let normal = if with_rng(|rng| rng.gen::<f32>()) < self.transmittance {
+            -1. * self.normal
+        } else {
+            self.normal
+        };
+        CosinePdf { normal }.generate()
+    }
+}
+
+/// `pdf` is `None` for delta (specular) scattering that cannot be evaluated for an arbitrary
+/// direction.
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub scattered: Ray,
+    pub pdf: Option<Box<dyn Pdf>>,
 }
 
 pub trait Material {
-    fn on_hit(&self, ray: &Ray, rec: &Hit) -> OnHit;
+    /// `None` means the ray was absorbed.
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord>;
+    fn emit(&self, _rec: &Hit) -> Color {
+        Color::ZERO
+    }
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>().rsplit("::").next().unwrap()
+    }
+    fn is_emissive(&self) -> bool {
+        false
+    }
+    /// Index of the medium enclosed by surfaces of this material, for materials that refract.
+    fn refraction_index(&self) -> Option<f32> {
+        None
+    }
+    fn sample_pdf(&self, _wo: Vector, _wi: Vector, _normal: Vector) -> f32 {
+        0.
+    }
+    fn albedo(&self, _rec: &Hit) -> Color {
+        Color::ZERO
+    }
+    fn blender_python(&self) -> String {
+        String::new()
+    }
+    /// Feeds every parameter of the material into `state`. Materials that don't override
+    /// this hash only a generic tag.
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("material");
+    }
 }
 
 pub struct Lambertian {
@@ -251,23 +924,97 @@ impl Lambertian {
     pub fn new(albedo: Color) -> Self {
         Self { albedo }
     }
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+        Lambertian::new(Color::from_rgb8(r, g, b))
+    }
 }
 
 impl Material for Lambertian {
-    fn on_hit(&self, ray: &Ray, rec: &Hit) -> OnHit {
-        let scatter_direction = rec.normal + Vector::random_unit();
-        let scatter_direction = if scatter_direction.near_zero() {
-            rec.normal
-        } else {
-            scatter_direction
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let pdf = CosinePdf { normal: rec.normal };
+        Some(ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray {
+                origin: ray.at(rec.t),
+                direction: pdf.generate(),
+                wavelength: ray.wavelength,
+                time: ray.time,
+            },
+            pdf: Some(Box::new(pdf)),
+        })
+    }
+    fn sample_pdf(&self, _wo: Vector, wi: Vector, normal: Vector) -> f32 {
+        CosinePdf { normal }.value(wi)
+    }
+    fn albedo(&self, _rec: &Hit) -> Color {
+        self.albedo
+    }
+    fn blender_python(&self) -> String {
+        [
+            blender::input("Base Color", blender::color(self.albedo)),
+            blender::input("Roughness", 1.0),
+        ]
+        .concat()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("lambertian");
+        state.write_vector(self.albedo);
+    }
+}
+
+pub struct LambertianTranslucent {
+    albedo: Color,
+    transmittance: f32,
+}
+
+impl LambertianTranslucent {
+    pub fn new(albedo: Color, transmittance: f32) -> Self {
+        Self {
+            albedo,
+            transmittance: transmittance.clamp(0., 1.),
+        }
+    }
+}
+
+impl Material for LambertianTranslucent {
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let pdf = TranslucentPdf {
+            normal: rec.normal,
+            transmittance: self.transmittance,
         };
-        OnHit::Scatter {
+        Some(ScatterRecord {
             attenuation: self.albedo,
             scattered: Ray {
                 origin: ray.at(rec.t),
-                direction: scatter_direction.normalize(),
+                direction: pdf.generate(),
+                wavelength: ray.wavelength,
+                time: ray.time,
             },
+            pdf: Some(Box::new(pdf)),
+        })
+    }
+    fn sample_pdf(&self, _wo: Vector, wi: Vector, normal: Vector) -> f32 {
+        TranslucentPdf {
+            normal,
+            transmittance: self.transmittance,
         }
+        .value(wi)
+    }
+    fn albedo(&self, _rec: &Hit) -> Color {
+        self.albedo
+    }
+    fn blender_python(&self) -> String {
+        [
+            blender::input("Base Color", blender::color(self.albedo)),
+            blender::input("Roughness", 1.0),
+            blender::input("Diffuse Transmission Weight", self.transmittance),
+        ]
+        .concat()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("translucent");
+        state.write_vector(self.albedo);
+        state.write_f32(self.transmittance);
     }
 }
 
@@ -276,39 +1023,108 @@ pub struct Metal {
     fuzz: f32,
 }
 
+const fn f0(n: f32, k: f32) -> f32 {
+    ((n - 1.) * (n - 1.) + k * k) / ((n + 1.) * (n + 1.) + k * k)
+}
+
 impl Metal {
+    pub const GOLD: Metal = Metal::from_ior(
+        Color::new(0.143, 0.374, 1.442),
+        Color::new(3.983, 2.385, 1.603),
+    );
+    pub const SILVER: Metal = Metal::from_ior(
+        Color::new(0.155, 0.117, 0.138),
+        Color::new(4.828, 3.122, 2.147),
+    );
+    pub const COPPER: Metal = Metal::from_ior(
+        Color::new(0.200, 0.924, 1.102),
+        Color::new(3.912, 2.452, 2.142),
+    );
+    pub const ALUMINUM: Metal = Metal::from_ior(
+        Color::new(1.657, 0.880, 0.521),
+        Color::new(9.224, 6.270, 4.837),
+    );
     pub fn new(albedo: Color, fuzz: f32) -> Self {
         Self {
             albedo,
             fuzz: fuzz.clamp(0., 1.),
         }
     }
+    /// Maps perceptual roughness to fuzz as `fuzz = roughness²`, matching GGX `alpha`.
+    pub fn new_with_roughness(albedo: Color, roughness: f32) -> Self {
+        let roughness = roughness.clamp(0., 1.);
+        Metal::new(albedo, roughness * roughness)
+    }
+    /// Normal-incidence reflectance of a conductor with complex IOR `n + ik`, sampled at 650/550/450 nm.
+    pub const fn from_ior(n: Color, k: Color) -> Self {
+        Self {
+            albedo: Color::new(f0(n.x, k.x), f0(n.y, k.y), f0(n.z, k.z)),
+            fuzz: 0.,
+        }
+    }
 }
 
 impl Material for Metal {
-    fn on_hit(&self, ray: &Ray, rec: &Hit) -> OnHit {
-        let reflected = ray.direction.reflect(rec.normal) + self.fuzz * Vector::random_unit();
-        if reflected.dot(rec.normal) > 0. {
-            OnHit::Scatter {
-                attenuation: self.albedo,
-                scattered: Ray {
-                    origin: ray.at(rec.t),
-                    direction: reflected,
-                },
-            }
-        } else {
-            OnHit::None
-        }
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let reflected = (ray.direction.normalize().reflect(rec.normal)
+            + self.fuzz * Vector::random_unit())
+        .normalize();
+        (reflected.dot(rec.normal) > 0.).then(|| ScatterRecord {
+            attenuation: self.albedo,
+            scattered: Ray {
+                origin: ray.at(rec.t),
+                direction: reflected,
+                wavelength: ray.wavelength,
+                time: ray.time,
+            },
+            pdf: None,
+        })
+    }
+    fn albedo(&self, _rec: &Hit) -> Color {
+        self.albedo
+    }
+    fn blender_python(&self) -> String {
+        [
+            blender::input("Base Color", blender::color(self.albedo)),
+            blender::input("Metallic", 1.0),
+            blender::input("Roughness", self.fuzz.sqrt()),
+        ]
+        .concat()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("metal");
+        state.write_vector(self.albedo);
+        state.write_f32(self.fuzz);
     }
 }
 
 pub struct Dielectric {
     refraction_index: f32,
+    dispersion: Option<(f32, f32)>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f32) -> Self {
-        Self { refraction_index }
+        Self {
+            refraction_index,
+            dispersion: None,
+        }
+    }
+    /// Cauchy dispersion `n(λ) = base_ior + b / λ² + c / λ⁴` with `λ` in micrometres.
+    pub fn with_dispersion(base_ior: f32, b: f32, c: f32) -> Self {
+        Self {
+            refraction_index: base_ior,
+            dispersion: Some((b, c)),
+        }
+    }
+    fn refraction_index_at(&self, wavelength: Option<f32>) -> f32 {
+        match (self.dispersion, wavelength) {
+            (Some((b, c)), Some(wavelength)) => {
+                let l2 = (wavelength * 1e-3).powi(2);
+                self.refraction_index + b / l2 + c / (l2 * l2)
+            }
+            _ => self.refraction_index,
+        }
     }
     fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
         let r0 = (1. - refraction_index) / (1. + refraction_index);
@@ -318,48 +1134,516 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn on_hit(&self, ray: &Ray, rec: &Hit) -> OnHit {
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let (wavelength, attenuation) = match (self.dispersion, ray.wavelength) {
+            (Some(_), None) => {
+                let wavelength = with_rng(|rng| rng.gen_range(VISIBLE_MIN..VISIBLE_MAX));
+                (Some(wavelength), wavelength_weight(wavelength))
+            }
+            _ => (ray.wavelength, Color::new(1., 1., 1.)),
+        };
+        let refraction_index = self.refraction_index_at(wavelength);
         let ri = if rec.is_front {
-            1. / self.refraction_index
+            rec.outside_ior / refraction_index
         } else {
-            self.refraction_index
+            refraction_index / rec.outside_ior
         };
         let cos_theta = f32::min((-1. * ray.direction).dot(rec.normal), 1.);
         let sin_theta = (1. - cos_theta * cos_theta).sqrt();
         let cannot_refract = ri * sin_theta > 1.;
-        OnHit::Scatter {
-            attenuation: Color::new(1., 1., 1.),
+        Some(ScatterRecord {
+            attenuation,
             scattered: Ray {
                 origin: ray.at(rec.t),
                 direction: if cannot_refract
-                    || Dielectric::reflectance(cos_theta, ri) > thread_rng().gen()
+                    || Dielectric::reflectance(cos_theta, ri) > with_rng(|rng| rng.gen())
                 {
                     ray.direction.reflect(rec.normal)
                 } else {
                     ray.direction.refract(rec.normal, ri)
                 },
+                wavelength,
+                time: ray.time,
             },
+            pdf: None,
+        })
+    }
+    fn refraction_index(&self) -> Option<f32> {
+        Some(self.refraction_index)
+    }
+    fn albedo(&self, _rec: &Hit) -> Color {
+        Color::WHITE
+    }
+    fn blender_python(&self) -> String {
+        [
+            blender::input("Transmission Weight", 1.0),
+            blender::input("Roughness", 0.0),
+            blender::input("IOR", self.refraction_index),
+        ]
+        .concat()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("dielectric");
+        state.write_f32(self.refraction_index);
+        let (b, c) = self.dispersion.unwrap_or((0., 0.));
+        state.write_f32(b);
+        state.write_f32(c);
+    }
+}
+
+pub struct Iridescent {
+    base: Arc<dyn Material + Sync + Send>,
+    film_ior: f32,
+    min_thickness_nm: f32,
+    max_thickness_nm: f32,
+    noise: Perlin,
+}
+
+impl Iridescent {
+    const NOISE_SCALE: f32 = 4.;
+    pub fn new(
+        base: Arc<dyn Material + Sync + Send>,
+        film_ior: f32,
+        min_thickness_nm: f32,
+        max_thickness_nm: f32,
+    ) -> Self {
+        Self {
+            base,
+            film_ior,
+            min_thickness_nm,
+            max_thickness_nm,
+            noise: Perlin::new(),
+        }
+    }
+    fn thickness(&self, p: Point) -> f32 {
+        if self.min_thickness_nm == self.max_thickness_nm {
+            self.min_thickness_nm
+        } else {
+            let a = (0.5 * (1. + self.noise.noise(Iridescent::NOISE_SCALE * p))).clamp(0., 1.);
+            self.min_thickness_nm + a * (self.max_thickness_nm - self.min_thickness_nm)
         }
     }
+    fn film_color(&self, cos_theta: f32, thickness: f32) -> Color {
+        let sin_theta_t = (1. - cos_theta * cos_theta).sqrt() / self.film_ior;
+        let cos_theta_t = (1. - sin_theta_t * sin_theta_t).sqrt();
+        let reflectance = |wavelength: f32| {
+            let delta = 4. * PI * self.film_ior * thickness * cos_theta_t / wavelength;
+            0.5 * (1. - delta.cos())
+        };
+        Color::new(reflectance(650.), reflectance(532.), reflectance(450.))
+    }
+}
+
+impl Material for Iridescent {
+    fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+        let scatter = self.base.on_hit(ray, rec)?;
+        let cos_theta = f32::min((-1. * ray.direction).dot(rec.normal), 1.);
+        let thickness = self.thickness(ray.at(rec.t));
+        Some(ScatterRecord {
+            attenuation: scatter.attenuation * self.film_color(cos_theta, thickness),
+            ..scatter
+        })
+    }
+    fn emit(&self, rec: &Hit) -> Color {
+        self.base.emit(rec)
+    }
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+    fn refraction_index(&self) -> Option<f32> {
+        self.base.refraction_index()
+    }
+    fn sample_pdf(&self, wo: Vector, wi: Vector, normal: Vector) -> f32 {
+        self.base.sample_pdf(wo, wi, normal)
+    }
+    fn albedo(&self, rec: &Hit) -> Color {
+        self.base.albedo(rec)
+    }
+    fn blender_python(&self) -> String {
+        self.base.blender_python()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("iridescent");
+        self.base.hash_content(state);
+        state.write_f32(self.film_ior);
+        state.write_f32(self.min_thickness_nm);
+        state.write_f32(self.max_thickness_nm);
+    }
 }
 
 pub struct Light {
     color: Color,
+    enabled: bool,
 }
 
 impl Light {
     pub fn new(color: Color) -> Self {
-        Self { color }
+        Self {
+            color,
+            enabled: true,
+        }
+    }
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
     }
 }
 
 impl Material for Light {
-    fn on_hit(&self, _ray: &Ray, _rec: &Hit) -> OnHit {
-        OnHit::Emitted { color: self.color }
+    fn on_hit(&self, _ray: &Ray, _rec: &Hit) -> Option<ScatterRecord> {
+        None
+    }
+    fn emit(&self, _rec: &Hit) -> Color {
+        if self.enabled {
+            self.color
+        } else {
+            Color::ZERO
+        }
+    }
+    fn is_emissive(&self) -> bool {
+        true
+    }
+    fn blender_python(&self) -> String {
+        let strength = self.color.x.max(self.color.y).max(self.color.z);
+        if !self.enabled || strength <= 0. {
+            return String::new();
+        }
+        [
+            blender::input("Emission Color", blender::color(self.color / strength)),
+            blender::input("Emission Strength", strength),
+        ]
+        .concat()
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("light");
+        state.write_vector(self.color);
+        state.write_bool(self.enabled);
     }
 }
 
+pub struct Normal;
+
+impl Material for Normal {
+    fn on_hit(&self, _ray: &Ray, _rec: &Hit) -> Option<ScatterRecord> {
+        None
+    }
+    fn emit(&self, rec: &Hit) -> Color {
+        0.5 * (rec.normal + Color::new(1., 1., 1.))
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("normal");
+    }
+}
+
+pub trait Background {
+    fn sample(&self, ray: &Ray) -> Color;
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("background");
+    }
+}
+
+pub struct SolidBackground {
+    pub color: Color,
+}
+
+impl Background for SolidBackground {
+    fn sample(&self, _ray: &Ray) -> Color {
+        self.color
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("solid");
+        state.write_vector(self.color);
+    }
+}
+
+pub struct GradientBackground {
+    pub top: Color,
+    pub bottom: Color,
+    /// Unit direction that maps to `top`.
+    pub up: Vector,
+    /// Cosine to `up` at which the horizon sits; negative values lower it.
+    pub horizon: f32,
+    /// How quickly the blend saturates away from the horizon; 1 reaches `top`/`bottom` at ±`up`.
+    pub sharpness: f32,
+}
+
+impl GradientBackground {
+    pub fn new(top: Color, bottom: Color) -> Self {
+        GradientBackground {
+            top,
+            bottom,
+            up: Vector::new(0., 1., 0.),
+            horizon: 0.,
+            sharpness: 1.,
+        }
+    }
+    pub fn sky_blue() -> Self {
+        GradientBackground::new(Color::new(0.5, 0.7, 1.0), Color::WHITE)
+    }
+    pub fn with_up(self, up: Vector) -> Self {
+        GradientBackground {
+            up: up.normalize(),
+            ..self
+        }
+    }
+    pub fn with_horizon(self, horizon: f32, sharpness: f32) -> Self {
+        GradientBackground {
+            horizon: horizon.clamp(-0.999, 0.999),
+            sharpness,
+            ..self
+        }
+    }
+}
+
+impl Background for GradientBackground {
+    fn sample(&self, ray: &Ray) -> Color {
+        let cos = ray.direction.normalize().dot(self.up) - self.horizon;
+        let s = cos
+            / if cos > 0. {
+                1. - self.horizon
+            } else {
+                1. + self.horizon
+            };
+        let a = 0.5 + 0.5 * (self.sharpness * s).clamp(-1., 1.);
+        (1.0 - a) * self.bottom + a * self.top
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("gradient");
+        state.write_vector(self.top);
+        state.write_vector(self.bottom);
+        state.write_vector(self.up);
+        state.write_f32(self.horizon);
+        state.write_f32(self.sharpness);
+    }
+}
+
+#[derive(Clone)]
 pub struct Object {
-    pub shape: Box<dyn Hittable + Sync>,
+    pub name: String,
+    pub shape: Arc<dyn Hittable + Sync + Send>,
     pub material: Arc<dyn Material + Sync + Send>,
+    pub cast_shadow: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct World {
+    objects: Vec<Object>,
+    children: Vec<Arc<World>>,
+}
+
+impl World {
+    pub fn new(objects: Vec<Object>) -> Self {
+        World {
+            objects,
+            children: Vec::new(),
+        }
+    }
+    pub fn hit_object(&self, ray: &Ray, interval: &Interval) -> Option<(Hit, &Object)> {
+        self.hit_object_from(ray, interval, 0)
+    }
+    fn hit_object_from(
+        &self,
+        ray: &Ray,
+        interval: &Interval,
+        first_id: usize,
+    ) -> Option<(Hit, &Object)> {
+        let mut closest: Option<(Hit, &Object)> = None;
+        for (id, object) in self.objects.iter().enumerate() {
+            let t_max = closest.as_ref().map_or(interval.max, |(h, _)| h.t);
+            let interval = Interval::new(interval.min, t_max);
+            let (center, radius) = object.shape.bounding_sphere();
+            if !ray.may_hit_sphere(center, radius, &interval) {
+                continue;
+            }
+            #[cfg(feature = "stats")]
+            stats::count_intersection_test();
+            if let Some(h) = object.shape.hit(ray, &interval) {
+                closest = Some((
+                    Hit {
+                        object_id: first_id + id,
+                        ..h
+                    },
+                    object,
+                ));
+            }
+        }
+        let mut first_id = first_id + self.objects.len();
+        for child in self.children.iter() {
+            let t_max = closest.as_ref().map_or(interval.max, |(h, _)| h.t);
+            if let Some(hit) =
+                child.hit_object_from(ray, &Interval::new(interval.min, t_max), first_id)
+            {
+                closest = Some(hit);
+            }
+            first_id += child.objects_count();
+        }
+        closest
+    }
+    pub fn objects(&self) -> Box<dyn Iterator<Item = &Object> + '_> {
+        Box::new(
+            self.objects
+                .iter()
+                .chain(self.children.iter().flat_map(|child| child.objects())),
+        )
+    }
+    pub fn objects_mut(&mut self) -> Box<dyn Iterator<Item = &mut Object> + '_> {
+        Box::new(
+            self.objects.iter_mut().chain(
+                self.children
+                    .iter_mut()
+                    .flat_map(|child| Arc::make_mut(child).objects_mut()),
+            ),
+        )
+    }
+    pub fn objects_count(&self) -> usize {
+        self.objects.len()
+            + self
+                .children
+                .iter()
+                .map(|child| child.objects_count())
+                .sum::<usize>()
+    }
+    pub fn push(&mut self, object: Object) {
+        self.objects.push(object);
+    }
+    pub fn add_child(&mut self, child: Arc<World>) {
+        self.children.push(child);
+    }
+}
+
+impl Hittable for World {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+        self.hit_object(ray, interval).map(|(h, _)| h)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.objects().fold(Aabb::EMPTY, |b, object| {
+            b.union(&object.shape.bounding_box())
+        })
+    }
+    /// Independent of the order objects were added in.
+    fn hash_content(&self, state: &mut ContentHasher) {
+        let mut objects: Vec<u64> = self
+            .objects()
+            .map(|object| {
+                let mut state = ContentHasher::new();
+                state.write_str(&object.name);
+                state.write_bool(object.cast_shadow);
+                object.shape.hash_content(&mut state);
+                object.material.hash_content(&mut state);
+                state.finish()
+            })
+            .collect();
+        objects.sort_unstable();
+        state.write_u64(objects.len() as u64);
+        objects.into_iter().for_each(|h| state.write_u64(h));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iridescent_forwards_to_its_base() {
+        let light = Iridescent::new(
+            Arc::new(Light::new(Color::new(1., 1., 1.))),
+            1.3,
+            300.,
+            300.,
+        );
+        assert!(light.is_emissive());
+        let base: Arc<dyn Material + Sync + Send> =
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let film = Iridescent::new(base.clone(), 1.3, 250., 450.);
+        assert!(!film.is_emissive());
+        let normal = Vector::new(0., 1., 0.);
+        let wo = Vector::new(0., 1., 1.).normalize();
+        let wi = Vector::new(1., 2., 0.).normalize();
+        assert!(film.sample_pdf(wo, wi, normal) > 0.);
+        assert_eq!(
+            film.sample_pdf(wo, wi, normal),
+            base.sample_pdf(wo, wi, normal)
+        );
+        assert_eq!(film.name(), "Iridescent");
+    }
+
+    #[test]
+    fn sphere_culling_works_with_unnormalized_directions() {
+        // A unit sphere 10 away, approached at 5 units of distance per unit of `t`.
+        let center = Point::new(0., 0., -10.);
+        let ray = Ray::new(Point::ZERO, Vector::new(0., 0., -5.));
+        assert!(ray.may_hit_sphere(center, 1., &Interval::new(0., 1.9)));
+        assert!(ray.may_hit_sphere(center, 1., &Interval::new(2.1, 3.)));
+        assert!(!ray.may_hit_sphere(center, 1., &Interval::new(0., 1.7)));
+        assert!(!ray.may_hit_sphere(center, 1., &Interval::new(2.3, f32::INFINITY)));
+        let past = Ray::new(Point::ZERO, Vector::new(0., 0.6, -3.));
+        assert!(!past.may_hit_sphere(center, 1., &Interval::RENDER_RANGE));
+
+        let metal = Metal::new(Color::new(0.8, 0.8, 0.8), 0.5);
+        let sphere = Sphere {
+            center,
+            radius: 1.,
+            velocity: None,
+        };
+        let hit = sphere.hit(&ray, &Interval::RENDER_RANGE).unwrap();
+        for _ in 0..16 {
+            if let Some(scatter) = metal.on_hit(&ray, &hit) {
+                assert!((scatter.scattered.direction.length() - 1.).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn metal_roughness_squares_into_fuzz_and_clamps() {
+        let fuzz = |roughness| Metal::new_with_roughness(Color::new(1., 1., 1.), roughness).fuzz;
+        assert_eq!(fuzz(0.), 0.);
+        assert_eq!(fuzz(0.5), 0.25);
+        assert_eq!(fuzz(1.), 1.);
+        assert_eq!(fuzz(-0.5), 0.);
+        assert_eq!(fuzz(3.), 1.);
+    }
+
+    #[test]
+    fn hdr_packing_guards_against_a_bad_gamma() {
+        let grey = Color::new(0.25, 0.25, 0.25);
+        assert_eq!(hdr_to_u32(grey, 4., 2.2, SurfaceFormat::Rgb8), 0xffffff);
+        assert_eq!(hdr_to_u32(grey, 1., 2., SurfaceFormat::Rgb8), 0x7f7f7f);
+        let linear = hdr_to_u32(grey, 1., 1., SurfaceFormat::Rgb8);
+        for gamma in [0., -2., f32::NAN] {
+            assert_eq!(hdr_to_u32(grey, 1., gamma, SurfaceFormat::Rgb8), linear);
+        }
+    }
+
+    fn sky_along(background: &GradientBackground, direction: Vector) -> Color {
+        background.sample(&Ray {
+            origin: Point::ZERO,
+            direction,
+            wavelength: None,
+            time: 0.,
+        })
+    }
+
+    #[test]
+    fn gradient_blends_along_its_axis() {
+        let (top, bottom) = (Color::new(0.2, 0.4, 1.), Color::new(1., 0.8, 0.6));
+        let horizon = 0.5 * (top + bottom);
+        let up = Vector::new(1., 1., 0.).normalize();
+        let tilted = GradientBackground::new(top, bottom).with_up(up);
+        assert_eq!(sky_along(&tilted, up), top);
+        assert_eq!(sky_along(&tilted, -1. * up), bottom);
+        for perpendicular in [Vector::new(1., -1., 0.), Vector::new(0., 0., 1.)] {
+            assert_eq!(sky_along(&tilted, perpendicular), horizon);
+        }
+        // World Y is no longer the gradient axis.
+        assert_ne!(sky_along(&tilted, Vector::new(0., 1., 0.)), top);
+
+        let low = GradientBackground::new(top, bottom).with_horizon(-0.5, 1.);
+        let at_horizon = Vector::new(3f32.sqrt() / 2., -0.5, 0.);
+        assert!((sky_along(&low, at_horizon) - horizon).length() < 1e-6);
+        assert_eq!(sky_along(&low, Vector::new(0., 1., 0.)), top);
+        assert_eq!(sky_along(&low, Vector::new(0., -1., 0.)), bottom);
+
+        let sharp = GradientBackground::new(top, bottom).with_horizon(0., 10.);
+        assert_eq!(sky_along(&sharp, Vector::new(1., 0.2, 0.)), top);
+        assert_eq!(sky_along(&sharp, Vector::new(1., 0., 0.)), horizon);
+    }
 }
@@ -0,0 +1,368 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(state: (u32, u32), bytes: &[u8]) -> (u32, u32) {
+    let (mut a, mut b) = state;
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (a, b)
+}
+
+fn chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = kind.to_vec();
+    body.extend_from_slice(data);
+    out.write_all(&body)?;
+    out.write_all(&crc32(&body).to_be_bytes())
+}
+
+/// Streams 8-bit RGB rows into an uncompressed (stored deflate) PNG. Each stored block goes out
+/// as its own IDAT chunk as soon as it is full, so only one block is ever buffered and the output
+/// doesn't depend on how the rows were split between calls.
+pub struct PngWriter<W: Write> {
+    out: W,
+    row_bytes: usize,
+    rows_left: u32,
+    /// Raw (filtered) bytes not yet handed to a block, including the ones in `block`.
+    remaining: usize,
+    block: Vec<u8>,
+    adler: (u32, u32),
+    started: bool,
+}
+
+impl<W: Write> PngWriter<W> {
+    pub fn new(mut out: W, width: u32, height: u32) -> io::Result<Self> {
+        let remaining = (width as usize * 3 + 1).checked_mul(height as usize);
+        let (Some(remaining), true) = (remaining, width > 0 && height > 0) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot encode a {width}x{height} PNG"),
+            ));
+        };
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        chunk(&mut out, b"IHDR", &header)?;
+        Ok(PngWriter {
+            out,
+            row_bytes: width as usize * 3,
+            rows_left: height,
+            remaining,
+            block: Vec::with_capacity(u16::MAX as usize),
+            adler: (1, 0),
+            started: false,
+        })
+    }
+    /// Appends whole rows of pixels, top to bottom.
+    pub fn write_rows(&mut self, rgb: &[u8]) -> io::Result<()> {
+        let rows = rgb.len() / self.row_bytes;
+        if !rgb.len().is_multiple_of(self.row_bytes) || rows > self.rows_left as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "PNG rows don't match the image size",
+            ));
+        }
+        for row in rgb.chunks(self.row_bytes) {
+            self.raw(&[0])?;
+            self.raw(row)?;
+        }
+        self.rows_left -= rows as u32;
+        Ok(())
+    }
+    /// Writes the trailer and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.rows_left > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("PNG is missing {} rows", self.rows_left),
+            ));
+        }
+        chunk(&mut self.out, b"IEND", &[])?;
+        Ok(self.out)
+    }
+    fn raw(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        while !bytes.is_empty() {
+            let n = bytes.len().min(u16::MAX as usize - self.block.len());
+            self.block.extend_from_slice(&bytes[..n]);
+            self.adler = adler32(self.adler, &bytes[..n]);
+            self.remaining -= n;
+            bytes = &bytes[n..];
+            if self.block.len() == u16::MAX as usize || self.remaining == 0 {
+                self.flush_block()?;
+            }
+        }
+        Ok(())
+    }
+    fn flush_block(&mut self) -> io::Result<()> {
+        let mut data = Vec::with_capacity(self.block.len() + 11);
+        if !self.started {
+            data.extend_from_slice(&[0x78, 0x01]);
+            self.started = true;
+        }
+        let len = self.block.len() as u16;
+        data.push((self.remaining == 0) as u8);
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(&(!len).to_le_bytes());
+        data.extend_from_slice(&self.block);
+        if self.remaining == 0 {
+            let (a, b) = self.adler;
+            data.extend_from_slice(&(b << 16 | a).to_be_bytes());
+        }
+        self.block.clear();
+        chunk(&mut self.out, b"IDAT", &data)
+    }
+}
+
+/// Encodes 8-bit RGB pixels as an uncompressed (stored deflate) PNG.
+pub fn encode_png(width: u32, height: u32, rgb: &[u8]) -> io::Result<Vec<u8>> {
+    let mut png = PngWriter::new(Vec::new(), width, height)?;
+    png.write_rows(rgb)?;
+    png.finish()
+}
+
+pub fn write_png(path: impl AsRef<Path>, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut png = PngWriter::new(BufWriter::new(File::create(path)?), width, height)?;
+    png.write_rows(rgb)?;
+    png.finish()?.flush()
+}
+
+/// Encodes float RGB pixels, top row first, as a little-endian PFM (rows stored bottom-up).
+pub fn encode_pfm(width: u32, height: u32, rgb: &[f32]) -> Vec<u8> {
+    let mut pfm = format!("PF\n{width} {height}\n-1.0\n").into_bytes();
+    for row in rgb.chunks(width as usize * 3).rev() {
+        for value in row {
+            pfm.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    pfm
+}
+
+pub fn write_pfm(path: impl AsRef<Path>, width: u32, height: u32, rgb: &[f32]) -> io::Result<()> {
+    std::fs::write(path, encode_pfm(width, height, rgb))
+}
+
+const BLOCK: usize = 8;
+
+/// Mean absolute RGB8 difference of each 8x8 block, scaled to `[0, 1]`.
+pub fn block_differences(a: &[u8], b: &[u8], width: u32, height: u32) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let mut differences = Vec::new();
+    for by in (0..height).step_by(BLOCK) {
+        for bx in (0..width).step_by(BLOCK) {
+            let mut sum = 0u32;
+            let mut count = 0u32;
+            for y in by..(by + BLOCK).min(height) {
+                let row = (y * width + bx) * 3..(y * width + (bx + BLOCK).min(width)) * 3;
+                for (p, q) in a[row.clone()].iter().zip(&b[row]) {
+                    sum += p.abs_diff(*q) as u32;
+                    count += 1;
+                }
+            }
+            differences.push(sum as f32 / (count as f32 * 255.));
+        }
+    }
+    differences
+}
+
+/// Nearest-rank percentile; 0 for an empty slice.
+pub fn percentile(values: &[f32], p: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = (p.clamp(0., 1.) * sorted.len() as f32).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied().unwrap_or(0.)
+}
+
+/// Stops once the 99th-percentile block difference between successive resolves stays under
+/// `threshold` for two checks in a row.
+pub struct PerceptualStop {
+    pub threshold: f32,
+    previous: Option<Vec<u8>>,
+    passes: u32,
+}
+
+impl PerceptualStop {
+    pub fn new(threshold: f32) -> Self {
+        PerceptualStop {
+            threshold,
+            previous: None,
+            passes: 0,
+        }
+    }
+    pub fn check(&mut self, image: &[u8], width: u32, height: u32) -> bool {
+        if let Some(previous) = &self.previous {
+            let difference = percentile(&block_differences(previous, image, width, height), 0.99);
+            self.passes = if difference < self.threshold {
+                self.passes + 1
+            } else {
+                0
+            };
+        }
+        self.previous = Some(image.to_vec());
+        self.passes >= 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; (width * height * 3) as usize]
+    }
+
+    /// Checks the chunk CRCs and unpacks the stored deflate stream back into filtered rows.
+    fn decode_raw(png: &[u8]) -> Vec<u8> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut zlib = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let body = &rest[4..8 + len];
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            if &body[..4] == b"IDAT" {
+                zlib.extend_from_slice(&body[4..]);
+            }
+            rest = &rest[12 + len..];
+        }
+        assert_eq!(zlib[..2], [0x78, 0x01]);
+        let mut raw = Vec::new();
+        let mut at = 2;
+        loop {
+            let last = zlib[at] == 1;
+            let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+            assert_eq!(
+                !len as u16,
+                u16::from_le_bytes([zlib[at + 3], zlib[at + 4]])
+            );
+            raw.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+            at += 5 + len;
+            if last {
+                break;
+            }
+        }
+        let (a, b) = adler32((1, 0), &raw);
+        assert_eq!(zlib[at..], (b << 16 | a).to_be_bytes());
+        raw
+    }
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height * 3)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn png_round_trips_through_several_blocks() {
+        let (width, height) = (300, 250);
+        let rgb = gradient(width, height);
+        let raw = decode_raw(&encode_png(width, height, &rgb).unwrap());
+        assert!(raw.len() > 2 * u16::MAX as usize);
+        let rows: Vec<u8> = raw
+            .chunks(width as usize * 3 + 1)
+            .flat_map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..].to_vec()
+            })
+            .collect();
+        assert_eq!(rows, rgb);
+    }
+
+    #[test]
+    fn png_bytes_do_not_depend_on_how_rows_are_split() {
+        let (width, height) = (130, 270);
+        let rgb = gradient(width, height);
+        let whole = encode_png(width, height, &rgb).unwrap();
+        for band in [1, 7, 64, 269] {
+            let mut png = PngWriter::new(Vec::new(), width, height).unwrap();
+            for rows in rgb.chunks(band * width as usize * 3) {
+                png.write_rows(rows).unwrap();
+            }
+            assert_eq!(png.finish().unwrap(), whole);
+        }
+    }
+
+    #[test]
+    fn png_rejects_bad_sizes() {
+        assert!(encode_png(0, 4, &[]).is_err());
+        assert!(encode_png(4, 0, &[]).is_err());
+        assert!(encode_png(2, 2, &[0; 9]).is_err());
+        let mut png = PngWriter::new(Vec::new(), 2, 2).unwrap();
+        png.write_rows(&[0; 6]).unwrap();
+        assert!(png.write_rows(&[0; 12]).is_err());
+        assert!(png.finish().is_err());
+    }
+
+    #[test]
+    fn block_differences_locate_known_changes() {
+        let a = image(16, 12, 100);
+        assert!(block_differences(&a, &a, 16, 12).iter().all(|&d| d == 0.));
+        let mut b = a.clone();
+        for y in 8..12 {
+            for x in 8..16 {
+                b[(y * 16 + x) * 3] = 151;
+            }
+        }
+        let differences = block_differences(&a, &b, 16, 12);
+        assert_eq!(differences.len(), 4);
+        assert_eq!(differences[..3], [0., 0., 0.]);
+        assert!((differences[3] - 51. / 3. / 255.).abs() < 1e-6);
+        assert_eq!(percentile(&differences, 0.99), differences[3]);
+        assert_eq!(percentile(&differences, 0.5), 0.);
+        assert_eq!(percentile(&[], 0.99), 0.);
+    }
+
+    #[test]
+    fn perceptual_stop_needs_two_quiet_checks_in_a_row() {
+        let quiet = image(8, 8, 100);
+        let loud = image(8, 8, 140);
+        let mut stop = PerceptualStop::new(0.05);
+        assert!(!stop.check(&quiet, 8, 8));
+        assert!(!stop.check(&quiet, 8, 8));
+        assert!(!stop.check(&loud, 8, 8));
+        assert!(!stop.check(&loud, 8, 8));
+        assert!(stop.check(&loud, 8, 8));
+
+        let mut stop = PerceptualStop::new(0.05);
+        let nearly = image(8, 8, 110);
+        stop.check(&quiet, 8, 8);
+        assert!(!stop.check(&nearly, 8, 8));
+        assert!(stop.check(&nearly, 8, 8));
+    }
+
+    #[test]
+    fn pfm_stores_rows_bottom_up() {
+        let rgb: Vec<f32> = (0..12).map(|i| i as f32).collect();
+        let pfm = encode_pfm(2, 2, &rgb);
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        let values: Vec<f32> = pfm[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(values[..6], rgb[6..]);
+        assert_eq!(values[6..], rgb[..6]);
+    }
+}
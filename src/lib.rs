@@ -0,0 +1,19 @@
+#[cfg(feature = "memory-stats")]
+pub mod alloc;
+pub mod blender;
+pub mod content_hash;
+pub mod conventions;
+pub mod curve;
+pub mod geometry;
+pub mod image;
+pub mod noise;
+pub mod rng;
+pub mod scene;
+pub mod scene_file;
+pub mod scene_graph;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod settings;
+pub mod spectrum;
+pub mod stats;
+pub mod transform;
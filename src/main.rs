@@ -1,142 +1,194 @@
-mod geometry;
-
-use std::{f32::consts::PI, num::NonZeroU32, rc::Rc, sync::Arc, time::SystemTime};
+use std::{
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    num::NonZeroU32,
+    rc::Rc,
+    str::FromStr,
+    sync::Arc,
+};
 
-use geometry::{
-    gamma, Color, Dielectric, Hit, Interval, Lambertian, Light, Material, Metal, Object, Point,
-    Ray, Sphere, Vector,
+use softbuffer::{Context, SoftBufferError, Surface};
+#[cfg(feature = "scripting")]
+use tracer::script::load_script;
+use tracer::{
+    conventions::vertical_fov,
+    geometry::{
+        Color, Dielectric, GradientBackground, Lambertian, Light, Metal, Object, Point, Sphere,
+        World,
+    },
+    image::{write_pfm, write_png, PngWriter},
+    scene::{ExposureState, RenderOptions, Scene, Severity},
+    scene_file::{count, load_scene, SceneLimits},
+    settings::Settings,
 };
-use rand::Rng;
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
-use softbuffer::{Buffer, Context, Surface};
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalSize,
-    event::{ElementState, KeyEvent, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
     event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::Window,
 };
 
-struct Scene {
-    camera_position: Point,
-    camera_direction: Vector,
-    camera_up: Vector,
-    camera_fov: f32,
-    max_samples: u32,
-    depth: u32,
-    objects: Vec<Object>,
-}
-
-impl Scene {
-    fn trace(&self, ray: &Ray, interval: &Interval, depth: u32) -> Color {
-        if depth == 0 {
-            Vector::ZERO
-        } else {
-            let mut hit: Option<Hit> = None;
-            let mut material: Option<Arc<dyn Material>> = None;
-            for object in self.objects.iter() {
-                let t_min = if let Some(Hit {
-                    t,
-                    normal: _,
-                    is_front: _,
-                }) = hit
-                {
-                    t
-                } else {
-                    interval.max
-                };
-                match object.shape.hit(ray, &Interval::new(interval.min, t_min)) {
-                    None => (),
-                    Some(h) => {
-                        if h.t < t_min {
-                            hit = Some(h);
-                            material = Some(object.material.clone());
-                        }
-                    }
-                }
-            }
-            if let Some(h) = hit {
-                match material.unwrap().on_hit(ray, &h) {
-                    geometry::OnHit::None => Vector::ZERO,
-                    geometry::OnHit::Scatter {
-                        attenuation,
-                        scattered,
-                    } => self.trace(&scattered, interval, depth - 1) * attenuation,
-                    geometry::OnHit::Emitted { color } => color,
-                }
-            } else {
-                // let a = 0.5 * (ray.direction.y + 1.0);
-                // (1.0 - a) * Vector::new(1.0, 1.0, 1.0) + a * Vector::new(0.5, 0.7, 1.0)
-                Color::ZERO
-            }
-        }
-    }
-    fn render(&self, buffer: &mut Buffer<Rc<Window>, Rc<Window>>, width: u32, height: u32) {
-        let start_time = SystemTime::now();
-        let camera_right = self.camera_direction.cross(self.camera_up).normalize();
-        let camera_up = camera_right.cross(self.camera_direction).normalize();
-        let l = width as f32 / (self.camera_fov / 2.).tan();
-        let contribution = 1.0 / (self.max_samples as f32);
-        buffer
-            .par_iter_mut()
-            .zip(0..width * height)
-            .for_each(|(pixel, index)| {
-                let mut rng = rand::thread_rng();
-                let y = (index / width) as f32 - height as f32 / 2.;
-                let x = (index % width) as f32 - width as f32 / 2.;
-                let mut vec_pixel = Color::ZERO;
-                for _ in 0..self.max_samples {
-                    vec_pixel = vec_pixel
-                        + contribution
-                            * self.trace(
-                                &Ray::new(
-                                    self.camera_position,
-                                    ((x + rng.gen::<f32>() - 0.5) * camera_right
-                                        - (y + rng.gen::<f32>() - 0.5) * camera_up
-                                        + l * self.camera_direction)
-                                        .normalize(),
-                                ),
-                                &Interval::RENDER_RANGE,
-                                self.depth,
-                            );
-                }
-                *pixel = gamma(vec_pixel);
-            });
-        let end_time = SystemTime::now();
-        println!(
-            "{}s",
-            end_time.duration_since(start_time).unwrap().as_secs_f64()
-        );
-    }
-}
+const DEBUG_PATHS: u32 = 16;
+/// Default `--memory-limit` in MiB for `--output`; larger renders are streamed in bands.
+const DEFAULT_MEMORY_LIMIT: usize = 4096;
+const BAND_ROWS: u32 = 64;
+const FOV_STEP: f32 = 5.;
+/// Samples per pixel of the quick render after an interactive camera change.
+const PREVIEW_SAMPLES: u32 = 4;
 
 struct App {
     window: Option<Rc<Window>>,
     context: Option<Context<Rc<Window>>>,
     surface: Option<Surface<Rc<Window>, Rc<Window>>>,
+    frame: Vec<u32>,
+    cursor: PhysicalPosition<f64>,
+    modifiers: ModifiersState,
+    settings: Settings,
     scene: Scene,
+    /// Scene path or `DEMO_SCENE`; camera bookmarks are kept per scene under this key.
+    scene_key: String,
+    /// Window and output size; starts from the settings unless given on the command line.
+    size: PhysicalSize<u32>,
+    /// Output settings from the command line; size and samples are filled in per render.
+    options: RenderOptions,
+    exposure_state: ExposureState,
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.window = Some(Rc::new(
-            event_loop
-                .create_window(
-                    Window::default_attributes()
-                        .with_inner_size(PhysicalSize::new(800, 450))
-                        .with_resizable(false),
-                )
-                .unwrap(),
+impl App {
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save() {
+            log::warn!("could not save settings: {e}");
+        }
+    }
+    fn debug_pixel(&self, x: u32, y: u32) {
+        let size = self.window.as_ref().unwrap().inner_size();
+        let report = self.scene.debug_pixel(x, y, &self.debug_options(size));
+        let written = fs::write("debug_pixel.json", report.to_json())
+            .and_then(|_| fs::write("debug_pixel.obj", report.to_obj()));
+        match written {
+            Ok(()) => log::info!("pixel ({x}, {y}): wrote debug_pixel.json and debug_pixel.obj"),
+            Err(e) => log::error!("pixel ({x}, {y}): {e}"),
+        }
+    }
+    /// Options that trace `DEBUG_PATHS` samples of a pixel the way a render of `size` would.
+    fn debug_options(&self, size: PhysicalSize<u32>) -> RenderOptions {
+        RenderOptions {
+            width: size.width,
+            height: size.height,
+            max_samples: DEBUG_PATHS,
+            depth: self.scene.depth,
+            ..self.options.clone()
+        }
+    }
+    fn print_lights(&self) {
+        for (name, enabled) in self.scene.lights() {
+            log::info!("light {name}: {}", if enabled { "on" } else { "off" });
+        }
+    }
+    fn aspect(&self) -> f32 {
+        let size = self.window.as_ref().map_or(self.size, |w| w.inner_size());
+        size.width.max(1) as f32 / size.height.max(1) as f32
+    }
+    fn update_title(&self) {
+        self.window.as_ref().unwrap().set_title(&format!(
+            "tracer - fov {:.0}\u{b0}",
+            vertical_fov(self.scene.camera_fov, self.aspect()).to_degrees()
         ));
-        self.context = Some(Context::new(self.window.as_ref().unwrap().clone()).unwrap());
-        self.surface = Some(
-            Surface::new(
-                self.context.as_ref().unwrap(),
-                self.window.as_ref().unwrap().clone(),
-            )
-            .unwrap(),
+    }
+    fn create_surface(&mut self) -> Result<(), SoftBufferError> {
+        let window = self.window.as_ref().unwrap().clone();
+        let context = Context::new(window.clone())?;
+        self.surface = Some(Surface::new(&context, window)?);
+        self.context = Some(context);
+        Ok(())
+    }
+    fn present(&mut self) -> Result<(), SoftBufferError> {
+        let size = self.window.as_ref().unwrap().inner_size();
+        let (Some(surface), Some(width), Some(height)) = (
+            self.surface.as_mut(),
+            NonZeroU32::new(size.width),
+            NonZeroU32::new(size.height),
+        ) else {
+            return Ok(());
+        };
+        if self.frame.len() != (size.width * size.height) as usize {
+            return Ok(());
+        }
+        surface.resize(width, height)?;
+        let mut buffer = surface.buffer_mut()?;
+        buffer.copy_from_slice(&self.frame);
+        buffer.present()
+    }
+    fn show_frame(&mut self) {
+        if let Err(e) = self.present() {
+            log::warn!("surface lost ({e}), recreating it");
+            if let Err(e) = self.create_surface().and_then(|_| self.present()) {
+                log::error!("could not recover surface: {e}");
+            }
+        }
+    }
+    fn redraw(&mut self) {
+        self.render_frame(self.scene.max_samples, self.options.perceptual_stop);
+    }
+    /// Renders at preview quality; Enter renders the full frame again.
+    fn preview(&mut self) {
+        self.render_frame(PREVIEW_SAMPLES.min(self.scene.max_samples), None);
+    }
+    fn render_frame(&mut self, samples: u32, perceptual_stop: Option<f32>) {
+        let (width, height) = {
+            let size = self.window.as_ref().unwrap().inner_size();
+            (size.width, size.height)
+        };
+        self.frame.resize((width * height) as usize, 0);
+        self.scene.render_smoothed(
+            &mut self.frame,
+            &RenderOptions {
+                width,
+                height,
+                max_samples: samples,
+                depth: self.scene.depth,
+                perceptual_stop,
+                ..self.options.clone()
+            },
+            &mut self.exposure_state,
         );
+        self.show_frame();
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.window.is_none() {
+            let mut attributes = Window::default_attributes()
+                .with_inner_size(self.size)
+                .with_resizable(false);
+            if let Some((x, y)) = self.settings.window_position {
+                attributes = attributes.with_position(PhysicalPosition::new(x, y));
+            }
+            self.window = Some(Rc::new(event_loop.create_window(attributes).unwrap()));
+            self.update_title();
+            self.print_lights();
+            for warning in self.scene.validate() {
+                match warning.severity {
+                    Severity::Warn => log::warn!("{}", warning.message),
+                    Severity::Error => log::error!("{}", warning.message),
+                }
+            }
+            let warm_up = self.scene.warm_up(&self.options);
+            log::debug!("warm-up {:.3}s", warm_up.as_secs_f64());
+        }
+        if let Err(e) = self.create_surface() {
+            log::error!("could not create surface: {e}");
+        }
+        self.show_frame();
+    }
+
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.surface = None;
+        self.context = None;
     }
 
     fn window_event(
@@ -147,10 +199,14 @@ impl ApplicationHandler for App {
     ) {
         match event {
             WindowEvent::CloseRequested => {
+                self.save_settings();
                 event_loop.exit();
             }
+            WindowEvent::Moved(position) => {
+                self.settings.window_position = Some((position.x, position.y));
+            }
             WindowEvent::RedrawRequested => {
-                self.window.as_ref().unwrap().request_redraw();
+                self.show_frame();
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -162,84 +218,563 @@ impl ApplicationHandler for App {
                     },
                 ..
             } => {
-                let (width, height) = {
-                    let size = self.window.as_ref().unwrap().inner_size();
-                    (size.width, size.height)
+                self.redraw();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key:
+                            PhysicalKey::Code(code @ (KeyCode::BracketLeft | KeyCode::BracketRight)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let mut camera = self.scene.camera_state();
+                camera.step_fov(fov_step(code), self.aspect());
+                self.scene.set_camera_state(camera);
+                self.update_title();
+                self.preview();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyC),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                self.scene.material_override = match self.scene.material_override {
+                    Some(_) => None,
+                    None => self.scene.override_material("clay").ok(),
                 };
-                self.surface
-                    .as_mut()
-                    .unwrap()
-                    .resize(
-                        NonZeroU32::new(width).unwrap(),
-                        NonZeroU32::new(height).unwrap(),
-                    )
-                    .unwrap();
-
-                let mut buffer = self.surface.as_mut().unwrap().buffer_mut().unwrap();
-                self.scene.render(&mut buffer, width, height);
-                buffer.present().unwrap();
+                self.settings.material_override =
+                    self.scene.material_override.as_ref().map(|_| "clay".into());
+                self.save_settings();
+                self.redraw();
+            }
+            #[cfg(feature = "stats")]
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyH),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let size = self.window.as_ref().unwrap().inner_size();
+                self.frame = self.scene.render_cost_heatmap(size.width, size.height);
+                self.show_frame();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyL),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let names: Vec<String> = self
+                    .scene
+                    .lights()
+                    .into_iter()
+                    .map(|(name, _)| name.into())
+                    .collect();
+                for name in names {
+                    self.scene.toggle_light(&name);
+                }
+                self.print_lights();
+                self.redraw();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if bookmark_slot(code).is_some() => {
+                let slot = bookmark_slot(code).unwrap();
+                if self.modifiers.control_key() {
+                    self.settings
+                        .store_bookmark(&self.scene_key, slot, self.scene.camera_state());
+                    self.save_settings();
+                    log::info!("stored camera bookmark {slot}");
+                } else if let Some(camera) = self.settings.bookmark(&self.scene_key, slot) {
+                    self.scene.set_camera_state(camera);
+                    self.update_title();
+                    self.redraw();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.modifiers.control_key() => {
+                self.debug_pixel(self.cursor.x as u32, self.cursor.y as u32);
             }
             _ => (),
         }
     }
 }
 
-fn main() {
-    let event_loop = EventLoop::new().unwrap();
+fn bookmark_slot(code: KeyCode) -> Option<u32> {
+    match code {
+        KeyCode::Digit1 => Some(1),
+        KeyCode::Digit2 => Some(2),
+        KeyCode::Digit3 => Some(3),
+        KeyCode::Digit4 => Some(4),
+        KeyCode::Digit5 => Some(5),
+        KeyCode::Digit6 => Some(6),
+        KeyCode::Digit7 => Some(7),
+        KeyCode::Digit8 => Some(8),
+        KeyCode::Digit9 => Some(9),
+        _ => None,
+    }
+}
+
+fn stream_png(scene: &Scene, options: &RenderOptions, path: &str) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut png = PngWriter::new(file, options.width, options.height)?;
+    scene.render_bands(options, BAND_ROWS, |rgb| png.write_rows(rgb))?;
+    png.finish()?.flush()
+}
+
+/// `[` narrows the field of view and `]` widens it.
+fn fov_step(code: KeyCode) -> f32 {
+    if code == KeyCode::BracketLeft {
+        -FOV_STEP
+    } else {
+        FOV_STEP
+    }
+}
+
+fn parse_pixel(arg: &str) -> Option<(u32, u32)> {
+    let (x, y) = arg.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+fn parse_point(arg: &str) -> Option<Point> {
+    let v: Vec<f32> = arg
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [x, y, z] = v[..] else { return None };
+    Some(Point::new(x, y, z))
+}
+
+fn demo_scene() -> Scene {
     let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
     let material_center = Arc::new(Light::new(Color::new(5.0, 8.0, 10.0)));
     let material_left = Arc::new(Dielectric::new(1.5));
     let material_bubble = Arc::new(Dielectric::new(1. / 1.5));
-    let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
+    let material_right = Arc::new(Metal::GOLD);
+    Scene::new(World::new(vec![
+        Object {
+            name: "ground".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0.0, -100.5, -1.0),
+                radius: 100.0,
+                velocity: None,
+            }),
+            material: material_ground.clone(),
+            cast_shadow: true,
+        },
+        Object {
+            name: "center".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0.0, 0.5, -1.2),
+                radius: 0.5,
+                velocity: None,
+            }),
+            material: material_center.clone(),
+            cast_shadow: true,
+        },
+        Object {
+            name: "left".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(-1.0, 0.0, -1.0),
+                radius: 0.5,
+                velocity: None,
+            }),
+            material: material_left.clone(),
+            cast_shadow: true,
+        },
+        Object {
+            name: "bubble".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(-1.0, 0.0, -1.0),
+                radius: 0.4,
+                velocity: None,
+            }),
+            material: material_bubble.clone(),
+            cast_shadow: true,
+        },
+        Object {
+            name: "right".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(1.0, 0.0, -1.0),
+                radius: 0.5,
+                velocity: None,
+            }),
+            material: material_right.clone(),
+            cast_shadow: true,
+        },
+    ]))
+}
+
+/// Value following `name`; an error if the flag is given without one.
+fn flag<'a>(args: &'a [String], name: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|a| a == name) {
+        None => Ok(None),
+        Some(i) => args
+            .get(i + 1)
+            .map(|a| Some(a.as_str()))
+            .ok_or(format!("{name} expects a value")),
+    }
+}
+
+fn parsed_flag<T: FromStr>(args: &[String], name: &str) -> Result<Option<T>, String>
+where
+    T::Err: fmt::Display,
+{
+    flag(args, name)?
+        .map(|a| a.parse().map_err(|e| format!("{name} {a}: {e}")))
+        .transpose()
+}
+
+/// A positive window or output size; command-line sizes apply to this run only and are not
+/// saved into the settings.
+fn size_flag(args: &[String], name: &str, saved: u32) -> Result<u32, String> {
+    Ok(parsed_flag::<NonZeroU32>(args, name)?.map_or(saved.max(1), NonZeroU32::get))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load_script(path: &str) -> Result<Scene, String> {
+    Err(format!("{path}: built without the scripting feature"))
+}
+
+/// Bookmark key of the built-in scene.
+const DEMO_SCENE: &str = "<demo>";
+
+/// Absolute form of a scene path, so bookmarks and the last scene survive a change of directory.
+fn scene_key(path: &str) -> String {
+    fs::canonicalize(path).map_or(path.into(), |path| path.display().to_string())
+}
+
+/// Runs that write a file or report instead of opening the window; these take everything from the
+/// command line and ignore the saved preferences.
+fn headless(args: &[String]) -> bool {
+    [
+        "--output",
+        "--bench-json",
+        "--debug-pixel",
+        "--export-blender",
+        "--motion-vectors",
+    ]
+    .iter()
+    .any(|name| args.iter().any(|a| a == name))
+}
+
+/// Loads the scene named on the command line, or the last one opened, and returns it with its
+/// bookmark key.
+fn scene_from_args(args: &[String], settings: &mut Settings) -> Result<(Scene, String), String> {
+    let headless = headless(args);
+    let load = |path: &str| {
+        if path.ends_with(".rhai") {
+            load_script(path)
+        } else {
+            load_scene(path)
+        }
+    };
+    let (mut scene, key) = match (flag(args, "--scene")?, &settings.last_scene) {
+        (Some(path), _) => {
+            let scene = load(path)?;
+            let key = scene_key(path);
+            settings.last_scene = Some(key.clone());
+            (scene, key)
+        }
+        (None, Some(last)) if !headless => match load(last) {
+            Ok(scene) => (scene, last.clone()),
+            Err(e) => {
+                log::warn!("{e}; opening the demo scene");
+                (demo_scene(), DEMO_SCENE.into())
+            }
+        },
+        (None, _) => (demo_scene(), DEMO_SCENE.into()),
+    };
+    if let Some(name) = settings.material_override.as_ref().filter(|_| !headless) {
+        match scene.override_material(name) {
+            Ok(material) => scene.material_override = Some(material),
+            Err(e) => log::warn!("material override: {e}"),
+        }
+    }
+    let limits = SceneLimits::default();
+    if let Some(n) = flag(args, "--samples")? {
+        scene.max_samples =
+            count(n, "sample count", limits.max_samples).map_err(|e| format!("--samples: {e}"))?;
+    }
+    if let Some(n) = flag(args, "--depth")? {
+        scene.depth = count(n, "depth", limits.max_depth).map_err(|e| format!("--depth: {e}"))?;
+    }
+    Ok((scene, key))
+}
+
+/// Writes log records to stderr; `-v` adds debug output and `-q` keeps only warnings.
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", record.args());
+        }
+    }
+    fn flush(&self) {}
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let level = if args.iter().any(|a| a == "-q" || a == "--quiet") {
+        log::LevelFilter::Warn
+    } else if args.iter().any(|a| a == "-v" || a == "--verbose") {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    static LOGGER: Logger = Logger;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+    if let Err(e) = run(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut settings = Settings::load();
+    let (scene, scene_key) = scene_from_args(args, &mut settings)?;
+    let size = PhysicalSize::new(
+        size_flag(args, "--width", settings.window_width)?,
+        size_flag(args, "--height", settings.window_height)?,
+    );
     let mut app = App {
         window: None,
         context: None,
         surface: None,
-        scene: Scene {
-            camera_position: Point::ZERO,
-            camera_direction: Vector::new(0., 0., -1.).normalize(),
-            camera_up: Vector::new(0., 1., 0.),
-            camera_fov: 3. * PI / 4.,
-            objects: vec![
-                Object {
-                    shape: Box::new(Sphere {
-                        center: Point::new(0.0, -100.5, -1.0),
-                        radius: 100.0,
-                    }),
-                    material: material_ground.clone(),
-                },
-                Object {
-                    shape: Box::new(Sphere {
-                        center: Point::new(0.0, 0.5, -1.2),
-                        radius: 0.5,
-                    }),
-                    material: material_center.clone(),
-                },
-                Object {
-                    shape: Box::new(Sphere {
-                        center: Point::new(-1.0, 0.0, -1.0),
-                        radius: 0.5,
-                    }),
-                    material: material_left.clone(),
-                },
-                Object {
-                    shape: Box::new(Sphere {
-                        center: Point::new(-1.0, 0.0, -1.0),
-                        radius: 0.4,
-                    }),
-                    material: material_bubble.clone(),
-                },
-                Object {
-                    shape: Box::new(Sphere {
-                        center: Point::new(1.0, 0.0, -1.0),
-                        radius: 0.5,
-                    }),
-                    material: material_right.clone(),
-                },
-            ],
-            max_samples: 256,
-            depth: 32,
+        frame: Vec::new(),
+        cursor: PhysicalPosition::default(),
+        modifiers: ModifiersState::default(),
+        scene,
+        scene_key,
+        size,
+        options: if headless(args) {
+            RenderOptions::default()
+        } else {
+            RenderOptions {
+                tone_map: settings.tone_map,
+                exposure: settings.exposure,
+                ..Default::default()
+            }
         },
+        settings,
+        exposure_state: ExposureState::default(),
     };
-    let _ = event_loop.run_app(&mut app);
+    if let Some(name) = flag(args, "--override-material")? {
+        app.scene.material_override = Some(
+            app.scene
+                .override_material(name)
+                .map_err(|e| format!("--override-material: {e}"))?,
+        );
+    }
+    for (i, _) in args
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "--disable-light")
+    {
+        let name = args
+            .get(i + 1)
+            .ok_or("--disable-light expects an object name")?;
+        app.scene.set_light_enabled(name, false);
+    }
+    if args.iter().any(|a| a == "--no-parallel") {
+        app.options.pool = Some(RenderOptions::thread_pool(1)?);
+    }
+    if let Some(tone_map) = parsed_flag(args, "--tone-map")? {
+        app.options.tone_map = tone_map;
+    }
+    if let Some(exposure) = parsed_flag(args, "--exposure")? {
+        app.options.exposure = exposure;
+    }
+    app.options.perceptual_stop = parsed_flag(args, "--perceptual-stop")?;
+    if args.iter().any(|a| a == "--sky") {
+        app.scene.background = Arc::new(GradientBackground::sky_blue());
+    }
+    if let Some(path) = flag(args, "--export-blender")? {
+        return fs::write(path, app.scene.export_blender_python())
+            .map_err(|e| format!("{path}: {e}"));
+    }
+    if let Some(path) = flag(args, "--motion-vectors")? {
+        let mut previous = app.scene.camera_state();
+        if let Some(position) = flag(args, "--previous-camera")? {
+            previous.position = parse_point(position).ok_or("--previous-camera expects x,y,z")?;
+        }
+        let motion = app
+            .scene
+            .render_motion_vectors(&previous, size.width, size.height);
+        let rgb: Vec<f32> = motion.chunks(2).flat_map(|m| [m[0], m[1], 0.]).collect();
+        return write_pfm(path, size.width, size.height, &rgb).map_err(|e| format!("{path}: {e}"));
+    }
+    if let Some(path) = flag(args, "--output")? {
+        let options = RenderOptions {
+            width: size.width,
+            height: size.height,
+            max_samples: app.scene.max_samples,
+            depth: app.scene.depth,
+            ..app.options.clone()
+        };
+        let limit = parsed_flag::<usize>(args, "--memory-limit")?.unwrap_or(DEFAULT_MEMORY_LIMIT);
+        let bench = flag(args, "--bench-json")?;
+        let written = match options.check_memory(limit << 20) {
+            Ok(()) => {
+                let (rgb, stats) = app.scene.render_with_stats(&options);
+                if let Some(bench) = bench {
+                    fs::write(bench, stats.to_json()).map_err(|e| format!("{bench}: {e}"))?;
+                }
+                write_png(path, options.width, options.height, &rgb)
+            }
+            Err(e) if bench.is_some() => {
+                return Err(format!("--bench-json needs the whole film in memory: {e}"));
+            }
+            Err(e) => {
+                log::info!("{e}; streaming {BAND_ROWS}-row bands to {path}");
+                stream_png(&app.scene, &options, path)
+            }
+        };
+        return written.map_err(|e| format!("{path}: {e}"));
+    }
+    if let Some(pixel) = flag(args, "--debug-pixel")? {
+        let (x, y) = parse_pixel(pixel)
+            .filter(|&(x, y)| x < size.width && y < size.height)
+            .ok_or(format!(
+                "--debug-pixel expects x,y within {}x{}",
+                size.width, size.height
+            ))?;
+        let report = app.scene.trace_pixel_paths(x, y, &app.debug_options(size));
+        println!("{}", report.to_json());
+        if let Some(path) = flag(args, "--debug-obj")? {
+            fs::write(path, report.to_obj()).map_err(|e| format!("{path}: {e}"))?;
+        }
+        return Ok(());
+    }
+    let event_loop = EventLoop::new().map_err(|e| e.to_string())?;
+    event_loop.run_app(&mut app).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn flags_without_a_value_are_errors() {
+        assert_eq!(
+            flag(&args("tracer --scene a.scene"), "--scene"),
+            Ok(Some("a.scene"))
+        );
+        assert_eq!(flag(&args("tracer"), "--scene"), Ok(None));
+        assert!(flag(&args("tracer --scene"), "--scene").is_err());
+        assert!(scene_from_args(&args("tracer --scene"), &mut Settings::default()).is_err());
+        for line in [
+            "tracer --samples 0",
+            "tracer --depth 0",
+            "tracer --samples -1",
+        ] {
+            let Err(e) = scene_from_args(&args(line), &mut Settings::default()) else {
+                panic!("{line} accepted");
+            };
+            assert!(e.contains("bad"), "{e}");
+        }
+    }
+
+    #[test]
+    fn sizes_must_be_positive_and_stay_out_of_the_settings() {
+        let mut settings = Settings::default();
+        let line = args("tracer --width 320 --height 0");
+        assert_eq!(size_flag(&line, "--width", 800), Ok(320));
+        assert!(size_flag(&line, "--height", 450).is_err());
+        assert!(size_flag(&args("tracer --width -4"), "--width", 800).is_err());
+        assert_eq!(size_flag(&args("tracer"), "--width", 0), Ok(1));
+        let (_, key) =
+            scene_from_args(&args("tracer --width 320 --samples 3"), &mut settings).unwrap();
+        assert_eq!(key, DEMO_SCENE);
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn headless_runs_ignore_the_saved_override() {
+        let mut settings = Settings {
+            material_override: Some("clay".into()),
+            ..Default::default()
+        };
+        let (scene, _) = scene_from_args(&args("tracer"), &mut settings).unwrap();
+        assert!(scene.material_override.is_some());
+        for line in [
+            "tracer --output out.png",
+            "tracer --output out.png --bench-json bench.json",
+            "tracer --debug-pixel 1,1",
+            "tracer --motion-vectors mv.pfm",
+        ] {
+            let (scene, _) = scene_from_args(&args(line), &mut settings).unwrap();
+            assert!(scene.material_override.is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn bracket_keys_step_the_field_of_view_and_keep_the_view() {
+        let mut scene = demo_scene();
+        scene.camera_roll = 0.3;
+        let before = scene.camera_state();
+        let ray = scene.camera_ray(40., 30., 80, 60);
+        for code in [
+            KeyCode::BracketRight,
+            KeyCode::BracketRight,
+            KeyCode::BracketLeft,
+        ] {
+            let mut camera = scene.camera_state();
+            camera.step_fov(fov_step(code), 80. / 60.);
+            scene.set_camera_state(camera);
+        }
+        let after = scene.camera_state();
+        let vertical = |fov| vertical_fov(fov, 80. / 60.).to_degrees();
+        assert!((vertical(after.fov) - vertical(before.fov) - FOV_STEP).abs() < 1e-3);
+        assert_eq!(
+            (after.position, after.direction),
+            (before.position, before.direction)
+        );
+        assert!((after.up - before.up).length() < 1e-6);
+        // Only the field of view changed, so the centre of the view stays put.
+        let centre = scene.camera_ray(40., 30., 80, 60);
+        assert!((centre.direction - ray.direction).length() < 1e-5);
+    }
 }
@@ -0,0 +1,95 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::geometry::{Point, Vector};
+
+const POINT_COUNT: usize = 256;
+/// Seed of `Perlin::new`, so the same scene always gets the same noise.
+const DEFAULT_SEED: u64 = 0x5eed;
+
+pub struct Perlin {
+    vectors: Vec<Vector>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Perlin {
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_SEED)
+    }
+    pub fn with_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let vectors = (0..POINT_COUNT)
+            .map(|_| {
+                Vector::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+                .normalize()
+            })
+            .collect();
+        let mut permutation = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        Perlin {
+            vectors,
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+        }
+    }
+    pub fn noise(&self, p: Point) -> f32 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        let (uu, vv, ww) = (
+            u * u * (3. - 2. * u),
+            v * v * (3. - 2. * v),
+            w * w * (3. - 2. * w),
+        );
+        let mut sum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let vector = self.vectors[self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize]];
+                    let (fi, fj, fk) = (di as f32, dj as f32, dk as f32);
+                    let weight = Vector::new(u - fi, v - fj, w - fk);
+                    sum += (fi * uu + (1. - fi) * (1. - uu))
+                        * (fj * vv + (1. - fj) * (1. - vv))
+                        * (fk * ww + (1. - fk) * (1. - ww))
+                        * vector.dot(weight);
+                }
+            }
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_reproducible_per_seed() {
+        let points = [
+            Point::new(0.3, 1.7, -2.2),
+            Point::new(10.5, 0.1, 4.9),
+            Point::new(-3.3, -7.25, 0.6),
+        ];
+        let sample = |perlin: &Perlin| points.map(|p| perlin.noise(p));
+        assert_eq!(sample(&Perlin::new()), sample(&Perlin::new()));
+        assert_eq!(sample(&Perlin::with_seed(9)), sample(&Perlin::with_seed(9)));
+        assert_ne!(sample(&Perlin::with_seed(9)), sample(&Perlin::new()));
+    }
+}
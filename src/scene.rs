@@ -0,0 +1,2362 @@
+#[cfg(feature = "stats")]
+use std::sync::atomic::Ordering;
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    fmt, io,
+    ops::Range,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+#[cfg(feature = "stats")]
+use rayon::iter::IntoParallelIterator;
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator},
+    slice::{ParallelSlice, ParallelSliceMut},
+    ThreadPool, ThreadPoolBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "memory-stats")]
+use crate::alloc;
+#[cfg(feature = "stats")]
+use crate::geometry::heat;
+use crate::{
+    blender,
+    content_hash::ContentHasher,
+    conventions::{
+        camera_to_ndc, fov_from_vertical, ndc_to_camera_ray, ndc_to_screen, screen_to_ndc,
+        vertical_fov, CameraBasis,
+    },
+    geometry::{
+        gamma, gamma_rgb8, Aabb, Background, Color, GradientBackground, Hit, Hittable,
+        Intersection, Interval, Lambertian, Material, Normal, Object, Point, Ray, RayDifferential,
+        SolidBackground, Vector, World,
+    },
+    image::PerceptualStop,
+    rng,
+    stats::{self, ShapeStats, ShapeType},
+};
+
+const MISS_LENGTH: f32 = 10.;
+/// Object pairs `Scene::validate` compares before giving up on the overlap check.
+const MAX_OVERLAP_CHECKS: usize = 1_000_000;
+/// Samples per pixel in each pass of a render with `perceptual_stop`.
+const PASS_SAMPLES: u32 = 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ToneMap {
+    #[default]
+    Clamp,
+    Reinhard,
+    SoftClip {
+        shoulder: f32,
+    },
+}
+
+impl ToneMap {
+    /// Linear up to `shoulder`, then rolls highlights off towards 1 while keeping their hue.
+    pub fn soft_clip(shoulder: f32) -> Result<ToneMap, String> {
+        if shoulder > 0. && shoulder < 1. {
+            Ok(ToneMap::SoftClip { shoulder })
+        } else {
+            Err(format!(
+                "soft clip shoulder must be between 0 and 1, got {shoulder}"
+            ))
+        }
+    }
+    pub fn apply(self, c: Color) -> Color {
+        match self {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => Color::new(c.x / (1. + c.x), c.y / (1. + c.y), c.z / (1. + c.z)),
+            ToneMap::SoftClip { shoulder } => {
+                let shoulder = shoulder.clamp(0., 0.999);
+                let peak = c.x.max(c.y).max(c.z);
+                if peak <= shoulder {
+                    c
+                } else {
+                    let range = 1. - shoulder;
+                    let rolled = shoulder + range * (1. - (-(peak - shoulder) / range).exp());
+                    c * (rolled / peak)
+                }
+            }
+        }
+    }
+    pub fn hash_content(self, state: &mut ContentHasher) {
+        match self {
+            ToneMap::Clamp => state.write_u64(0),
+            ToneMap::Reinhard => state.write_u64(1),
+            ToneMap::SoftClip { shoulder } => {
+                state.write_u64(2);
+                state.write_f32(shoulder);
+            }
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+    /// `clamp`, `reinhard`, or `soft-clip` with an optional `:shoulder` (0.8 by default).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "clamp" => Ok(ToneMap::Clamp),
+            None if s == "reinhard" => Ok(ToneMap::Reinhard),
+            None if s == "soft-clip" => ToneMap::soft_clip(0.8),
+            Some(("soft-clip", shoulder)) => ToneMap::soft_clip(
+                shoulder
+                    .parse()
+                    .map_err(|_| format!("bad soft clip shoulder {shoulder:?}"))?,
+            ),
+            _ => Err(format!(
+                "unknown tone map {s:?}; expected clamp, reinhard or soft-clip[:shoulder]"
+            )),
+        }
+    }
+}
+
+const MIDDLE_GRAY: f32 = 0.18;
+
+fn luminance(c: &[f32]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Exposure {
+    Manual(f32),
+    Auto { min: f32, max: f32 },
+    Highlight { min: f32, max: f32, percentile: f32 },
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Manual(1.)
+    }
+}
+
+impl Exposure {
+    pub fn hash_content(self, state: &mut ContentHasher) {
+        match self {
+            Exposure::Manual(value) => {
+                state.write_u64(0);
+                state.write_f32(value);
+            }
+            Exposure::Auto { min, max } => {
+                state.write_u64(1);
+                state.write_f32(min);
+                state.write_f32(max);
+            }
+            Exposure::Highlight {
+                min,
+                max,
+                percentile,
+            } => {
+                state.write_u64(2);
+                state.write_f32(min);
+                state.write_f32(max);
+                state.write_f32(percentile);
+            }
+        }
+    }
+    pub fn meter(self, hdr: &[f32]) -> f32 {
+        let (min, max) = match self {
+            Exposure::Manual(exposure) => return exposure,
+            Exposure::Auto { min, max } | Exposure::Highlight { min, max, .. } => (min, max),
+        };
+        let mut luminances: Vec<f32> = hdr
+            .chunks(3)
+            .map(luminance)
+            .filter(|l| l.is_finite() && *l > 0.)
+            .collect();
+        if luminances.is_empty() {
+            return 1.;
+        }
+        let log_mean = luminances.iter().map(|l| l.ln()).sum::<f32>() / luminances.len() as f32;
+        let mut exposure = MIDDLE_GRAY / log_mean.exp();
+        if let Exposure::Highlight { percentile, .. } = self {
+            luminances.sort_by(f32::total_cmp);
+            let index = ((luminances.len() - 1) as f32 * percentile.clamp(0., 1.)) as usize;
+            exposure = exposure.min(1. / luminances[index]);
+        }
+        exposure.clamp(min, max)
+    }
+}
+
+impl FromStr for Exposure {
+    type Err = String;
+    /// A manual multiplier such as `1.5`, `auto`, or `highlight` with an optional
+    /// `:percentile` (0.99 by default).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = (0.01, 100.);
+        match s.split_once(':') {
+            None if s == "auto" => Ok(Exposure::Auto { min, max }),
+            None if s == "highlight" => Ok(Exposure::Highlight {
+                min,
+                max,
+                percentile: 0.99,
+            }),
+            Some(("highlight", percentile)) => match percentile.parse() {
+                Ok(percentile) if (0. ..=1.).contains(&percentile) => Ok(Exposure::Highlight {
+                    min,
+                    max,
+                    percentile,
+                }),
+                _ => Err(format!("bad highlight percentile {percentile:?}")),
+            },
+            _ => match s.parse() {
+                Ok(value) if value > 0. && f32::is_finite(value) => Ok(Exposure::Manual(value)),
+                _ => Err(format!(
+                    "unknown exposure {s:?}; expected a positive number, auto or highlight[:percentile]"
+                )),
+            },
+        }
+    }
+}
+
+/// Exponentially smoothed auto exposure, so progressive passes and successive frames don't
+/// flicker as the metering settles.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExposureState {
+    value: Option<f32>,
+}
+
+impl ExposureState {
+    /// Weight of each new metering, applied in log space.
+    const RATE: f32 = 0.3;
+
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+    /// Meters `hdr` and eases the running value towards it; manual exposure is used as is.
+    pub fn meter(&mut self, exposure: Exposure, hdr: &[f32]) -> f32 {
+        if let Exposure::Manual(value) = exposure {
+            self.value = Some(value);
+            return value;
+        }
+        let metered = exposure.meter(hdr);
+        let value = match self.value {
+            Some(previous) if previous > 0. => previous * (metered / previous).powf(Self::RATE),
+            _ => metered,
+        };
+        self.value = Some(value);
+        value
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct RenderStats {
+    #[serde(serialize_with = "stats::seconds")]
+    pub preprocess: Duration,
+    #[serde(serialize_with = "stats::seconds")]
+    pub render: Duration,
+    #[serde(serialize_with = "stats::seconds")]
+    pub resolve: Duration,
+    pub rays: Option<u64>,
+    pub hits: Option<u64>,
+    /// Primitive tests, hits and time per shape type, with the `stats` feature.
+    #[serde(serialize_with = "stats::shape_stats")]
+    pub shapes: Option<ShapeStats>,
+    pub memory: Option<MemoryStats>,
+    /// Samples per pixel that were taken, and what ended the render.
+    pub samples: u32,
+    pub stop: Option<StopReason>,
+    /// Exposure multiplier the film was resolved with; copy it into `Exposure::Manual` to pin it.
+    pub exposure: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StopReason {
+    /// `max_samples` were taken.
+    Samples,
+    /// Successive passes no longer changed the resolved image visibly.
+    Perceptual,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StopReason::Samples => "sample cap",
+            StopReason::Perceptual => "converged",
+        })
+    }
+}
+
+/// Live heap bytes at each render phase, with the `memory-stats` feature.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct MemoryStats {
+    pub scene: usize,
+    pub preprocess: usize,
+    pub film: usize,
+    pub peak: usize,
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1 << 20) as f64;
+        write!(
+            f,
+            "scene {:.1} MiB, preprocess {:.1} MiB, film {:.1} MiB, peak {:.1} MiB",
+            mib(self.scene),
+            mib(self.preprocess),
+            mib(self.film),
+            mib(self.peak)
+        )
+    }
+}
+
+impl RenderStats {
+    pub fn total(&self) -> Duration {
+        self.preprocess + self.render + self.resolve
+    }
+    pub fn rays_per_second(&self) -> Option<f64> {
+        let seconds = self.render.as_secs_f64();
+        Some(self.rays? as f64 / seconds).filter(|_| seconds > 0.)
+    }
+    /// JSON for bench reports, with durations in seconds and counters that were not collected
+    /// as `null`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("render stats serialize to JSON")
+    }
+    /// Adds the timings and counters of `other`, e.g. of a later pass over the same film.
+    pub fn merge(&mut self, other: &RenderStats) {
+        let add = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.preprocess += other.preprocess;
+        self.render += other.render;
+        self.resolve += other.resolve;
+        self.rays = add(self.rays, other.rays);
+        self.hits = add(self.hits, other.hits);
+        self.shapes = match (self.shapes, other.shapes) {
+            (Some(mut shapes), Some(more)) => {
+                for (counter, more) in shapes.iter_mut().zip(more) {
+                    counter.tests += more.tests;
+                    counter.hits += more.hits;
+                    counter.time += more.time;
+                }
+                Some(shapes)
+            }
+            (a, b) => a.or(b),
+        };
+        self.memory = other.memory.or(self.memory);
+        self.samples += other.samples;
+        self.stop = other.stop.or(self.stop);
+        self.exposure = other.exposure.or(self.exposure);
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.3}s (preprocess {:.3}s, render {:.3}s, resolve {:.3}s)",
+            self.total().as_secs_f64(),
+            self.preprocess.as_secs_f64(),
+            self.render.as_secs_f64(),
+            self.resolve.as_secs_f64()
+        )?;
+        if let (Some(rays), Some(hits)) = (self.rays, self.hits) {
+            write!(f, ", {rays} rays, {hits} hits")?;
+        }
+        if let Some(rate) = self.rays_per_second() {
+            write!(f, ", {:.2} Mrays/s", rate / 1e6)?;
+        }
+        if let Some(shapes) = self.shapes {
+            for shape in ShapeType::ALL {
+                let counter = shapes[shape as usize];
+                if counter.tests > 0 {
+                    write!(
+                        f,
+                        ", {} {}/{} hits {:.3}s",
+                        shape.name(),
+                        counter.hits,
+                        counter.tests,
+                        counter.time.as_secs_f64()
+                    )?;
+                }
+            }
+        }
+        if let Some(stop) = self.stop {
+            write!(f, ", {} spp ({stop})", self.samples)?;
+        }
+        if let Some(exposure) = self.exposure {
+            write!(f, ", exposure {exposure:.4}")?;
+        }
+        if let Some(memory) = self.memory {
+            write!(f, ", memory: {memory}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub max_samples: u32,
+    pub depth: u32,
+    pub tone_map: ToneMap,
+    pub exposure: Exposure,
+    pub seed: Option<u64>,
+    /// Renders in passes and stops early once the 99th-percentile 8x8 block difference between
+    /// successive resolves stays below this for two passes.
+    pub perceptual_stop: Option<f32>,
+    /// Pool to render in, the global rayon pool when `None`. Build it once with
+    /// [`RenderOptions::thread_pool`] and share it between renders.
+    pub pool: Option<Arc<ThreadPool>>,
+}
+
+impl RenderOptions {
+    /// Stable hash of everything that affects the rendered pixels; `pool` is left out.
+    pub fn content_hash(&self) -> u64 {
+        let mut state = ContentHasher::new();
+        state.write_u64(self.width as u64);
+        state.write_u64(self.height as u64);
+        state.write_u64(self.max_samples as u64);
+        state.write_u64(self.depth as u64);
+        self.tone_map.hash_content(&mut state);
+        self.exposure.hash_content(&mut state);
+        state.write_bool(self.seed.is_some());
+        state.write_u64(self.seed.unwrap_or(0));
+        state.write_bool(self.perceptual_stop.is_some());
+        state.write_f32(self.perceptual_stop.unwrap_or(0.));
+        state.finish()
+    }
+    /// A pool of `num_threads` render threads; one thread renders sequentially.
+    pub fn thread_pool(num_threads: usize) -> Result<Arc<ThreadPool>, String> {
+        ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map(Arc::new)
+            .map_err(|e| format!("could not start {num_threads} render threads: {e}"))
+    }
+    /// Bytes for the film, the RGB8 output and a display buffer; `None` when the image has more
+    /// pixels than can be indexed or its size overflows.
+    pub fn memory_estimate(&self) -> Option<usize> {
+        let pixels = (self.width as usize).checked_mul(self.height as usize)?;
+        u32::try_from(pixels).ok()?;
+        pixels.checked_mul(3 * size_of::<f32>() + 3 + size_of::<u32>())
+    }
+    pub fn check_memory(&self, limit: usize) -> Result<(), String> {
+        let Some(estimate) = self.memory_estimate() else {
+            return Err(format!(
+                "{}x{} is too large to render",
+                self.width, self.height
+            ));
+        };
+        if estimate > limit {
+            Err(format!(
+                "{}x{} needs ~{} MiB, over the {} MiB limit; render in bands instead",
+                self.width,
+                self.height,
+                estimate >> 20,
+                limit >> 20
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            width: 800,
+            height: 450,
+            max_samples: 256,
+            depth: 32,
+            tone_map: ToneMap::default(),
+            exposure: Exposure::default(),
+            seed: None,
+            perceptual_stop: None,
+            pool: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BounceEvent {
+    pub origin: Point,
+    pub direction: Vector,
+    #[serde(flatten)]
+    pub hit: Option<BounceHit>,
+    pub event: &'static str,
+    pub color: Color,
+}
+
+#[derive(Serialize)]
+pub struct BounceHit {
+    pub t: f32,
+    pub object: String,
+    pub material: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct DebugSample {
+    pub color: Color,
+    pub bounces: Vec<BounceEvent>,
+}
+
+/// Paths traced through one pixel by the production sampler, for `--debug-pixel`.
+#[derive(Serialize)]
+pub struct DebugPixelReport {
+    pub pixel: (u32, u32),
+    pub samples: Vec<DebugSample>,
+}
+
+impl DebugPixelReport {
+    pub fn print(&self) {
+        for (i, sample) in self.samples.iter().enumerate() {
+            let color = sample.color;
+            log::debug!("sample {i}: color ({}, {}, {})", color.x, color.y, color.z);
+            let mut throughput = Color::new(1., 1., 1.);
+            for (bounce, b) in sample.bounces.iter().enumerate() {
+                throughput = throughput * b.color;
+                match &b.hit {
+                    Some(hit) => log::debug!(
+                        "  {bounce}: t={} {} ({}) {} throughput ({}, {}, {})",
+                        hit.t,
+                        hit.object,
+                        hit.material,
+                        b.event,
+                        throughput.x,
+                        throughput.y,
+                        throughput.z
+                    ),
+                    None => log::debug!("  {bounce}: {}", b.event),
+                }
+            }
+        }
+    }
+    /// JSON with non-finite numbers written as `null`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("debug reports serialize to JSON")
+    }
+    pub fn to_obj(&self) -> String {
+        let mut obj = String::new();
+        let mut count = 0;
+        for path in self.samples.iter().map(|sample| &sample.bounces) {
+            let start = count + 1;
+            for b in path {
+                obj += &format!("v {} {} {}\n", b.origin.x, b.origin.y, b.origin.z);
+                count += 1;
+            }
+            if let Some(b) = path.last() {
+                let end =
+                    Ray::new(b.origin, b.direction).at(b.hit.as_ref().map_or(MISS_LENGTH, |h| h.t));
+                obj += &format!("v {} {} {}\n", end.x, end.y, end.z);
+                count += 1;
+            }
+            let indices: Vec<String> = (start..=count).map(|i| i.to_string()).collect();
+            obj += &format!("l {}\n", indices.join(" "));
+        }
+        obj
+    }
+}
+
+fn format_count(n: u64) -> String {
+    match n {
+        0..1_000_000 => n.to_string(),
+        1_000_000..1_000_000_000 => format!("{:.1} million", n as f64 / 1e6),
+        _ => format!("{:.1} billion", n as f64 / 1e9),
+    }
+}
+
+#[derive(Debug)]
+pub enum Severity {
+    Warn,
+    Error,
+}
+
+pub struct ValidationWarning {
+    pub severity: Severity,
+    pub message: String,
+}
+
+const PARALLEL_UP: f32 = 1e-6;
+
+fn up_is_degenerate(direction: Vector, up: Vector) -> bool {
+    direction.normalize().cross(up.normalize()).length_square() < PARALLEL_UP
+}
+
+/// Forward, right and up vectors of a camera. An up vector parallel to `direction` is replaced
+/// by the world axis along `direction`'s smallest component; `roll` then turns the basis
+/// about the view axis.
+fn camera_basis(direction: Vector, up: Vector, roll: f32) -> CameraBasis {
+    let forward = direction.normalize();
+    let up = if up_is_degenerate(forward, up) {
+        let (x, y, z) = (forward.x.abs(), forward.y.abs(), forward.z.abs());
+        if x <= y && x <= z {
+            Vector::new(1., 0., 0.)
+        } else if y <= z {
+            Vector::new(0., 1., 0.)
+        } else {
+            Vector::new(0., 0., 1.)
+        }
+    } else {
+        up
+    };
+    let right = forward.cross(up).normalize();
+    let up = right.cross(forward).normalize();
+    let (sin, cos) = roll.sin_cos();
+    (forward, cos * right + sin * up, cos * up - sin * right)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: Point,
+    pub direction: Vector,
+    pub up: Vector,
+    pub fov: f32,
+}
+
+/// Vertical field of view limits in degrees for interactive adjustment.
+pub const MIN_FOV: f32 = 5.;
+pub const MAX_FOV: f32 = 170.;
+
+impl CameraState {
+    /// Widens the vertical field of view at `aspect` by `degrees` (narrows it when negative),
+    /// within `MIN_FOV` and `MAX_FOV`.
+    pub fn step_fov(&mut self, degrees: f32, aspect: f32) {
+        let vertical = (vertical_fov(self.fov, aspect).to_degrees() + degrees)
+            .clamp(MIN_FOV, MAX_FOV)
+            .to_radians();
+        self.fov = fov_from_vertical(vertical, aspect);
+    }
+    /// Pixel coordinates of `p` as seen by this camera, the inverse of `Scene::camera_ray`.
+    pub fn project(&self, p: Point, width: u32, height: u32) -> Option<(f32, f32)> {
+        let (x, y) = camera_to_ndc(
+            p - self.position,
+            width as f32 / height as f32,
+            self.fov,
+            camera_basis(self.direction, self.up, 0.),
+        )?;
+        Some(ndc_to_screen(x, y, width, height))
+    }
+}
+
+pub struct Scene {
+    pub camera_position: Point,
+    pub camera_direction: Vector,
+    pub camera_up: Vector,
+    pub camera_fov: f32,
+    pub camera_roll: f32,
+    pub max_samples: u32,
+    pub depth: u32,
+    world: World,
+    pub material_override: Option<Arc<dyn Material + Sync + Send>>,
+    shadow_casters: OnceLock<Vec<Arc<dyn Hittable + Sync + Send>>>,
+    /// Minimum hit distance for rays; derived from the scene's size when `None`.
+    pub ray_epsilon: Option<f32>,
+    extent: OnceLock<f32>,
+    pub background: Arc<dyn Background + Sync + Send>,
+    pub lights_enabled: HashMap<String, bool>,
+    /// Materials the scene file or script defined, by name.
+    materials: HashMap<String, Arc<dyn Material + Sync + Send>>,
+}
+
+impl Scene {
+    pub fn new(world: World) -> Self {
+        Scene {
+            camera_position: Point::ZERO,
+            camera_direction: Vector::new(0., 0., -1.),
+            camera_up: Vector::new(0., 1., 0.),
+            camera_fov: 3. * PI / 4.,
+            camera_roll: 0.,
+            max_samples: 256,
+            depth: 32,
+            world,
+            material_override: None,
+            shadow_casters: OnceLock::new(),
+            ray_epsilon: None,
+            extent: OnceLock::new(),
+            background: Arc::new(SolidBackground { color: Color::ZERO }),
+            lights_enabled: HashMap::new(),
+            materials: HashMap::new(),
+        }
+    }
+    /// Closest surface along `ray`, with the full surface frame and the material that will
+    /// shade it.
+    pub fn intersect(&self, ray: &Ray, interval: &Interval) -> Option<Intersection> {
+        let (h, object) = self.world.hit_object(ray, interval)?;
+        let point = ray.at(h.t);
+        let shading_normal = h.normal;
+        let helper = if shading_normal.x.abs() > 0.9 {
+            Vector::new(0., 1., 0.)
+        } else {
+            Vector::new(1., 0., 0.)
+        };
+        let tangent = helper.cross(shading_normal).normalize();
+        Some(Intersection {
+            t: h.t,
+            point,
+            geometric_normal: if h.is_front { h.normal } else { -1. * h.normal },
+            shading_normal,
+            tangent,
+            bitangent: shading_normal.cross(tangent),
+            uv: object.shape.uv(point),
+            object_id: h.object_id,
+            material: self.material(object).clone(),
+        })
+    }
+    pub fn trace(&self, ray: &Ray, interval: &Interval, depth: u32) -> Color {
+        let mut media = self.media_at(ray.origin);
+        self.trace_path(ray, interval, depth, 0, &mut media, None).0
+    }
+    /// Refracting objects that contain `p` as `(object_id, refraction_index)`, outermost first.
+    fn media_at(&self, p: Point) -> Vec<(usize, f32)> {
+        let mut media: Vec<(usize, f32, f32)> = self
+            .objects_iter()
+            .enumerate()
+            .filter(|(_, object)| object.shape.contains(p))
+            .filter_map(|(id, object)| {
+                let ior = self.material(object).refraction_index()?;
+                Some((id, ior, object.shape.bounding_sphere().1))
+            })
+            .collect();
+        media.sort_by(|a, b| b.2.total_cmp(&a.2));
+        media.into_iter().map(|(id, ior, _)| (id, ior)).collect()
+    }
+    fn trace_path(
+        &self,
+        ray: &Ray,
+        interval: &Interval,
+        depth: u32,
+        bounce: u32,
+        media: &mut Vec<(usize, f32)>,
+        path: Option<&mut Vec<BounceEvent>>,
+    ) -> (Color, u32) {
+        if depth == 0 {
+            (Vector::ZERO, 0)
+        } else {
+            #[cfg(feature = "stats")]
+            stats::RAY_COUNT.fetch_add(1, Ordering::Relaxed);
+            let hit = self.world.hit_object(ray, interval);
+            if let Some((h, object)) = hit {
+                #[cfg(feature = "stats")]
+                stats::HIT_COUNT.fetch_add(1, Ordering::Relaxed);
+                let outside_ior = media
+                    .iter()
+                    .rev()
+                    .find(|(id, _)| *id != h.object_id)
+                    .map_or(1., |&(_, ior)| ior);
+                let h = Hit {
+                    depth: bounce,
+                    outside_ior,
+                    ..h
+                };
+                let material = self.material(object);
+                let (emitted, scatter) =
+                    if material.is_emissive() && !self.light_enabled(&object.name) {
+                        (Color::ZERO, None)
+                    } else {
+                        (material.emit(&h), material.on_hit(ray, &h))
+                    };
+                let mut path = path;
+                if let Some(path) = path.as_mut() {
+                    let (event, color) = match &scatter {
+                        Some(scatter) => ("scatter", scatter.attenuation),
+                        None if emitted == Color::ZERO => ("absorbed", Color::ZERO),
+                        None => ("emitted", emitted),
+                    };
+                    path.push(BounceEvent {
+                        origin: ray.origin,
+                        direction: ray.direction,
+                        hit: Some(BounceHit {
+                            t: h.t,
+                            object: object.name.clone(),
+                            material: material.name(),
+                        }),
+                        event,
+                        color,
+                    });
+                }
+                match scatter {
+                    None => (emitted, 0),
+                    Some(scatter) => {
+                        if let Some(ior) = material.refraction_index() {
+                            if scatter.scattered.direction.dot(h.normal) < 0. {
+                                if h.is_front {
+                                    media.push((h.object_id, ior));
+                                } else if let Some(i) =
+                                    media.iter().rposition(|(id, _)| *id == h.object_id)
+                                {
+                                    media.remove(i);
+                                }
+                            }
+                        }
+                        let (color, bounces) = self.trace_path(
+                            &scatter.scattered,
+                            interval,
+                            depth - 1,
+                            bounce + 1,
+                            media,
+                            path,
+                        );
+                        (emitted + color * scatter.attenuation, bounces + 1)
+                    }
+                }
+            } else {
+                let color = self.background.sample(ray);
+                if let Some(path) = path {
+                    path.push(BounceEvent {
+                        origin: ray.origin,
+                        direction: ray.direction,
+                        hit: None,
+                        event: "miss",
+                        color,
+                    });
+                }
+                (color, 0)
+            }
+        }
+    }
+    pub fn light_enabled(&self, name: &str) -> bool {
+        self.lights_enabled.get(name).copied().unwrap_or(true)
+    }
+    pub fn set_light_enabled(&mut self, name: &str, enabled: bool) {
+        self.lights_enabled.insert(name.into(), enabled);
+    }
+    pub fn toggle_light(&mut self, name: &str) {
+        self.set_light_enabled(name, !self.light_enabled(name));
+    }
+    pub fn lights(&self) -> Vec<(&str, bool)> {
+        self.objects_iter()
+            .filter(|object| object.material.is_emissive())
+            .map(|object| (object.name.as_str(), self.light_enabled(&object.name)))
+            .collect()
+    }
+    pub fn set_background_gradient(&mut self, top: Color, bottom: Color) {
+        self.background = Arc::new(GradientBackground::new(top, bottom));
+    }
+    pub fn add_material(&mut self, name: &str, material: Arc<dyn Material + Sync + Send>) {
+        self.materials.insert(name.into(), material);
+    }
+    pub fn named_material(&self, name: &str) -> Option<&Arc<dyn Material + Sync + Send>> {
+        self.materials.get(name)
+    }
+    /// Material for `material_override`: `clay`, `white` and `normal` are built in, any other
+    /// name must be one of the scene's own materials.
+    pub fn override_material(&self, name: &str) -> Result<Arc<dyn Material + Sync + Send>, String> {
+        match name {
+            "clay" => Ok(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))),
+            "white" => Ok(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)))),
+            "normal" => Ok(Arc::new(Normal)),
+            _ => self.named_material(name).cloned().ok_or(format!(
+                "unknown material {name:?}; expected clay, normal, white or a scene material"
+            )),
+        }
+    }
+    fn material<'a>(&'a self, object: &'a Object) -> &'a Arc<dyn Material + Sync + Send> {
+        match &self.material_override {
+            Some(material) if !object.material.is_emissive() => material,
+            _ => &object.material,
+        }
+    }
+    pub fn render_albedo(&self, width: u32, height: u32) -> Vec<f32> {
+        let mut albedo = vec![0.; width as usize * height as usize * 3];
+        albedo
+            .par_chunks_mut(3)
+            .zip(0..width * height)
+            .for_each(|(pixel, index)| {
+                let ray = self.camera_ray(
+                    (index % width) as f32,
+                    (index / width) as f32,
+                    width,
+                    height,
+                );
+                if let Some((h, object)) = self.world.hit_object(&ray, &self.render_range()) {
+                    let c = self.material(object).albedo(&h);
+                    pixel.copy_from_slice(&[c.x, c.y, c.z]);
+                }
+            });
+        albedo
+    }
+    /// Index of each pixel's primary hit object, or `u32::MAX` where the ray escapes.
+    pub fn render_object_id_pass(&self, width: u32, height: u32) -> Vec<u32> {
+        let mut ids = vec![u32::MAX; width as usize * height as usize];
+        ids.par_iter_mut()
+            .zip(0..width * height)
+            .for_each(|(pixel, index)| {
+                let ray = self.camera_ray(
+                    (index % width) as f32,
+                    (index / width) as f32,
+                    width,
+                    height,
+                );
+                if let Some((h, _)) = self.world.hit_object(&ray, &self.render_range()) {
+                    *pixel = h.object_id as u32;
+                }
+            });
+        ids
+    }
+    /// Screen-space motion of each pixel's primary hit since `previous`, as (dx, dy) pairs.
+    /// Moving objects are taken to have travelled one shutter interval of their velocity.
+    /// Misses and points behind the previous camera are NaN.
+    pub fn render_motion_vectors(
+        &self,
+        previous: &CameraState,
+        width: u32,
+        height: u32,
+    ) -> Vec<f32> {
+        let mut motion = vec![f32::NAN; width as usize * height as usize * 2];
+        motion
+            .par_chunks_mut(2)
+            .zip(0..width * height)
+            .for_each(|(pixel, index)| {
+                let (x, y) = ((index % width) as f32, (index / width) as f32);
+                let ray = self.camera_ray(x, y, width, height);
+                let Some((h, object)) = self.world.hit_object(&ray, &self.render_range()) else {
+                    return;
+                };
+                let p = ray.at(h.t);
+                let before = p - object.shape.velocity_at(p);
+                if let Some((px, py)) = previous.project(before, width, height) {
+                    pixel.copy_from_slice(&[x - px, y - py]);
+                }
+            });
+        motion
+    }
+    /// Bounce at which each pixel's path first reaches a light or the background, divided by
+    /// `depth`. Paths that are absorbed or run out of bounces are NaN.
+    pub fn render_depth_pass(&self, width: u32, height: u32) -> Vec<f32> {
+        let mut depths = vec![f32::NAN; width as usize * height as usize];
+        depths
+            .par_iter_mut()
+            .zip(0..width * height)
+            .for_each(|(pixel, index)| {
+                let mut ray = self.camera_ray(
+                    (index % width) as f32,
+                    (index / width) as f32,
+                    width,
+                    height,
+                );
+                for bounce in 0..self.depth {
+                    let Some((h, object)) = self.world.hit_object(&ray, &self.render_range())
+                    else {
+                        *pixel = bounce as f32 / self.depth as f32;
+                        return;
+                    };
+                    let h = Hit { depth: bounce, ..h };
+                    let material = self.material(object);
+                    if material.is_emissive() && !self.light_enabled(&object.name) {
+                        return;
+                    }
+                    if material.emit(&h) != Color::ZERO {
+                        *pixel = bounce as f32 / self.depth as f32;
+                        return;
+                    }
+                    match material.on_hit(&ray, &h) {
+                        Some(scatter) => ray = scatter.scattered,
+                        None => return,
+                    }
+                }
+            });
+        depths
+    }
+    #[cfg(feature = "stats")]
+    pub fn render_cost_heatmap(&self, width: u32, height: u32) -> Vec<u32> {
+        let tests = self.intersection_test_counts(width, height);
+        let max = tests.iter().copied().max().unwrap_or(0).max(1);
+        tests
+            .into_iter()
+            .map(|count| heat(count as f32 / max as f32))
+            .collect()
+    }
+    /// Shape intersection tests run by each pixel's primary ray.
+    #[cfg(feature = "stats")]
+    pub fn intersection_test_counts(&self, width: u32, height: u32) -> Vec<u64> {
+        (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                stats::take_intersection_tests();
+                let ray = self.camera_ray(
+                    (index % width) as f32,
+                    (index / width) as f32,
+                    width,
+                    height,
+                );
+                self.world.hit_object(&ray, &self.render_range());
+                stats::take_intersection_tests()
+            })
+            .collect()
+    }
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+    /// Mutable access to the world; drops the caches derived from it.
+    pub fn world_mut(&mut self) -> &mut World {
+        self.invalidate_shadow_casters();
+        &mut self.world
+    }
+    pub fn set_world(&mut self, world: World) {
+        self.world = world;
+        self.invalidate_shadow_casters();
+    }
+    pub fn objects_iter(&self) -> impl Iterator<Item = &Object> {
+        self.world.objects()
+    }
+    pub fn objects_iter_mut(&mut self) -> impl Iterator<Item = &mut Object> {
+        self.world_mut().objects_mut()
+    }
+    pub fn objects_count(&self) -> usize {
+        self.world.objects_count()
+    }
+    pub fn invalidate_shadow_casters(&mut self) {
+        self.shadow_casters = OnceLock::new();
+        self.extent = OnceLock::new();
+    }
+    /// Length of the diagonal of the scene's bounding box, or 0 if it is empty or unbounded.
+    pub fn extent(&self) -> f32 {
+        *self.extent.get_or_init(|| {
+            let bounds = self.world.bounding_box();
+            Some((bounds.max - bounds.min).length())
+                .filter(|d| d.is_finite())
+                .unwrap_or(0.)
+        })
+    }
+    pub fn render_range(&self) -> Interval {
+        let epsilon = self
+            .ray_epsilon
+            .unwrap_or_else(|| (1e-6 * self.extent()).max(Interval::RENDER_RANGE.min));
+        Interval::new(epsilon, f32::INFINITY)
+    }
+    fn shadow_casters(&self) -> &[Arc<dyn Hittable + Sync + Send>] {
+        self.shadow_casters.get_or_init(|| {
+            self.objects_iter()
+                .filter(|object| object.cast_shadow)
+                .map(|object| object.shape.clone())
+                .collect()
+        })
+    }
+    pub fn visible(&self, a: Point, b: Point) -> bool {
+        let distance = (b - a).length();
+        let epsilon = self.render_range().min;
+        // Points closer than the ray epsilon can't be blocked, and `b - a` may not normalize.
+        if distance <= epsilon {
+            return true;
+        }
+        let casters = self.shadow_casters();
+        let ray = Ray::new(a, (b - a) / distance);
+        let interval = Interval::new(epsilon, distance - epsilon);
+        casters
+            .iter()
+            .all(|shape| shape.hit(&ray, &interval).is_none())
+    }
+    /// Stable hash of the camera, settings, background and world. Object order and the order
+    /// lights were toggled in do not affect it.
+    pub fn content_hash(&self) -> u64 {
+        let mut state = ContentHasher::new();
+        for v in [self.camera_position, self.camera_direction, self.camera_up] {
+            state.write_vector(v);
+        }
+        state.write_f32(self.camera_fov);
+        state.write_f32(self.camera_roll);
+        state.write_u64(self.max_samples as u64);
+        state.write_u64(self.depth as u64);
+        state.write_f32(self.ray_epsilon.unwrap_or(f32::NAN));
+        self.background.hash_content(&mut state);
+        match &self.material_override {
+            Some(material) => material.hash_content(&mut state),
+            None => state.write_str(""),
+        }
+        let mut lights: Vec<_> = self.lights_enabled.iter().collect();
+        lights.sort();
+        for (name, enabled) in lights {
+            state.write_str(name);
+            state.write_bool(*enabled);
+        }
+        self.world.hash_content(&mut state);
+        state.finish()
+    }
+    pub fn camera_state(&self) -> CameraState {
+        let (direction, _, up) =
+            camera_basis(self.camera_direction, self.camera_up, self.camera_roll);
+        CameraState {
+            position: self.camera_position,
+            direction,
+            up,
+            fov: self.camera_fov,
+        }
+    }
+    pub fn set_camera_state(&mut self, camera: CameraState) {
+        self.camera_position = camera.position;
+        self.camera_direction = camera.direction;
+        self.camera_up = camera.up;
+        self.camera_fov = camera.fov;
+        self.camera_roll = 0.;
+    }
+    pub fn camera_ray(&self, x: f32, y: f32, width: u32, height: u32) -> Ray {
+        let (x, y) = screen_to_ndc(x, y, width, height);
+        ndc_to_camera_ray(
+            x,
+            y,
+            width as f32 / height as f32,
+            self.camera_fov,
+            self.camera_position,
+            camera_basis(self.camera_direction, self.camera_up, self.camera_roll),
+        )
+    }
+    pub fn camera_ray_differential(
+        &self,
+        x: f32,
+        y: f32,
+        width: u32,
+        height: u32,
+    ) -> (Ray, RayDifferential) {
+        let rx = self.camera_ray(x + 1., y, width, height);
+        let ry = self.camera_ray(x, y + 1., width, height);
+        (
+            self.camera_ray(x, y, width, height),
+            RayDifferential {
+                rx_origin: rx.origin,
+                rx_direction: rx.direction,
+                ry_origin: ry.origin,
+                ry_direction: ry.direction,
+            },
+        )
+    }
+    /// Starts `options`' thread pool and fills lazy caches by rendering one pixel, so the first
+    /// real render's timing isn't inflated. Returns how long that took.
+    pub fn warm_up(&self, options: &RenderOptions) -> Duration {
+        let start = Instant::now();
+        self.shadow_casters();
+        self.extent();
+        let pixel = RenderOptions {
+            width: 1,
+            height: 1,
+            max_samples: 1,
+            depth: self.depth,
+            pool: options.pool.clone(),
+            ..Default::default()
+        };
+        Self::in_pool(&pixel, || self.render_hdr(&pixel));
+        start.elapsed()
+    }
+    pub fn render_to_hdr(&self, width: u32, height: u32) -> Vec<f32> {
+        self.render_hdr(&RenderOptions {
+            width,
+            height,
+            max_samples: self.max_samples,
+            depth: self.depth,
+            ..Default::default()
+        })
+    }
+    fn render_hdr(&self, options: &RenderOptions) -> Vec<f32> {
+        self.render_hdr_rows(options, 0..options.height)
+    }
+    /// Traces the samples of pixel `index` and hands each sample's color and bounce count to
+    /// `add`. With `paths`, every sample's bounces are recorded too.
+    fn sample_pixel(
+        &self,
+        options: &RenderOptions,
+        index: u32,
+        mut paths: Option<&mut Vec<Vec<BounceEvent>>>,
+        mut add: impl FnMut(Color, u32),
+    ) {
+        if let Some(seed) = options.seed {
+            rng::reseed(rng::pixel_seed(seed, index));
+        }
+        let y = (index / options.width) as f32;
+        let x = (index % options.width) as f32;
+        let camera_media = self.media_at(self.camera_position);
+        for _ in 0..options.max_samples {
+            let (dx, dy, time) =
+                rng::with_rng(|rng| (rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>()));
+            let ray = Ray {
+                time,
+                ..self.camera_ray(x + dx - 0.5, y + dy - 0.5, options.width, options.height)
+            };
+            let mut path = paths.as_ref().map(|_| Vec::new());
+            let (color, bounces) = self.trace_path(
+                &ray,
+                &self.render_range(),
+                options.depth,
+                0,
+                &mut camera_media.clone(),
+                path.as_mut(),
+            );
+            if let (Some(paths), Some(path)) = (paths.as_mut(), path) {
+                paths.push(path);
+            }
+            add(color, bounces);
+        }
+    }
+    fn render_hdr_rows(&self, options: &RenderOptions, rows: Range<u32>) -> Vec<f32> {
+        let width = options.width;
+        let mut hdr = vec![0.; width as usize * rows.len() * 3];
+        hdr.par_chunks_mut(3)
+            .zip(rows.start * width..rows.end * width)
+            .for_each(|(pixel, index)| {
+                let mut vec_pixel = Color::ZERO;
+                let contribution = 1.0 / (options.max_samples as f32);
+                self.sample_pixel(options, index, None, |color, _| {
+                    vec_pixel = vec_pixel + contribution * color
+                });
+                pixel.copy_from_slice(&[vec_pixel.x, vec_pixel.y, vec_pixel.z]);
+            });
+        hdr
+    }
+    /// Splits the image by the number of scattering events before the emitter or background
+    /// was reached: direct, one bounce, and two or more.
+    pub fn render_bounce_layers(&self, options: &RenderOptions) -> [Vec<f32>; 3] {
+        let pixels = options.width as usize * options.height as usize;
+        let mut layers = vec![Color::ZERO; pixels * 3];
+        layers
+            .par_chunks_mut(3)
+            .zip(0..options.width * options.height)
+            .for_each(|(pixel, index)| {
+                let contribution = 1.0 / (options.max_samples as f32);
+                self.sample_pixel(options, index, None, |color, bounces| {
+                    let layer = &mut pixel[bounces.min(2) as usize];
+                    *layer = *layer + contribution * color;
+                });
+            });
+        [0, 1, 2].map(|layer| {
+            layers
+                .chunks(3)
+                .flat_map(|pixel| [pixel[layer].x, pixel[layer].y, pixel[layer].z])
+                .collect()
+        })
+    }
+    /// Traces `options.max_samples` paths through pixel (`x`, `y`) with the same sampler and
+    /// seeding as a render with `options`, recording every bounce.
+    pub fn trace_pixel_paths(&self, x: u32, y: u32, options: &RenderOptions) -> DebugPixelReport {
+        let mut paths = Vec::new();
+        let mut colors = Vec::new();
+        self.sample_pixel(
+            options,
+            y * options.width + x,
+            Some(&mut paths),
+            |color, _| colors.push(color),
+        );
+        DebugPixelReport {
+            pixel: (x, y),
+            samples: colors
+                .into_iter()
+                .zip(paths)
+                .map(|(color, bounces)| DebugSample { color, bounces })
+                .collect(),
+        }
+    }
+    /// Traces the pixel like `trace_pixel_paths` and logs each bounce at debug level.
+    pub fn debug_pixel(&self, x: u32, y: u32, options: &RenderOptions) -> DebugPixelReport {
+        let report = self.trace_pixel_paths(x, y, options);
+        report.print();
+        report
+    }
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+        let mut error = |message: &str| {
+            warnings.push(ValidationWarning {
+                severity: Severity::Error,
+                message: message.into(),
+            })
+        };
+        if self.camera_direction.near_zero() {
+            error("camera direction has zero length");
+        }
+        if !(self.camera_fov > 0. && self.camera_fov < PI) {
+            error("camera fov must be between 0 and pi");
+        }
+        if self.max_samples == 0 {
+            error("max_samples is 0");
+        }
+        if self.depth == 0 {
+            error("depth is 0");
+        }
+        if !self.camera_direction.near_zero()
+            && up_is_degenerate(self.camera_direction, self.camera_up)
+        {
+            warnings.push(ValidationWarning {
+                severity: Severity::Warn,
+                message: "camera up is parallel to the view direction; using a fallback axis"
+                    .into(),
+            });
+        }
+        let extent = self.extent();
+        if extent > 1e6 || (extent > 0. && extent < 1e-3) {
+            warnings.push(ValidationWarning {
+                severity: Severity::Warn,
+                message: format!("scene is {extent} units across; check its units scale"),
+            });
+        }
+        if let Some(medium) = self.camera_medium() {
+            warnings.push(ValidationWarning {
+                severity: Severity::Warn,
+                message: format!("camera is inside {}", medium.name),
+            });
+        }
+        // Sweep along x so only objects whose boxes overlap there are compared.
+        let mut objects: Vec<(&Object, Aabb)> = self
+            .objects_iter()
+            .map(|object| (object, object.shape.bounding_box()))
+            .collect();
+        objects.sort_by(|(_, a), (_, b)| a.min.x.total_cmp(&b.min.x));
+        let buried = |inner: &Aabb, outer: &(&Object, Aabb)| {
+            outer.1.contains_box(inner)
+                && inner.corners().iter().all(|&p| outer.0.shape.contains(p))
+        };
+        let mut comparisons = 0;
+        'sweep: for (i, a) in objects.iter().enumerate() {
+            for b in objects[i + 1..]
+                .iter()
+                .take_while(|(_, b)| b.min.x <= a.1.max.x)
+            {
+                comparisons += 1;
+                if comparisons > MAX_OVERLAP_CHECKS {
+                    warnings.push(ValidationWarning {
+                        severity: Severity::Warn,
+                        message: format!(
+                            "stopped checking for overlapping objects after {MAX_OVERLAP_CHECKS} \
+                             comparisons"
+                        ),
+                    });
+                    break 'sweep;
+                }
+                let message = if a.1 == b.1 {
+                    format!("objects {} and {} fully overlap", a.0.name, b.0.name)
+                } else if buried(&a.1, b) {
+                    format!("object {} is buried inside {}", a.0.name, b.0.name)
+                } else if buried(&b.1, a) {
+                    format!("object {} is buried inside {}", b.0.name, a.0.name)
+                } else {
+                    continue;
+                };
+                warnings.push(ValidationWarning {
+                    severity: Severity::Warn,
+                    message,
+                });
+            }
+        }
+        warnings
+    }
+    pub fn camera_medium(&self) -> Option<&Object> {
+        self.objects_iter()
+            .filter(|object| object.shape.contains(self.camera_position))
+            .min_by(|a, b| {
+                a.shape
+                    .bounding_sphere()
+                    .1
+                    .total_cmp(&b.shape.bounding_sphere().1)
+            })
+    }
+    pub fn export_blender_python(&self) -> String {
+        let mut script = String::from(concat!(
+            "import bpy\n",
+            "from mathutils import Vector\n\n",
+            "bpy.ops.object.select_all(action=\"SELECT\")\n",
+            "bpy.ops.object.delete()\n",
+        ));
+        for object in self.objects_iter() {
+            let Some(shape) = object.shape.blender_python() else {
+                script += &format!("\n# {}: shape not supported\n", object.name);
+                continue;
+            };
+            script += &format!(
+                concat!(
+                    "\n{}obj.name = {:?}\n",
+                    "mat = bpy.data.materials.new({:?})\n",
+                    "mat.use_nodes = True\n",
+                    "bsdf = mat.node_tree.nodes[\"Principled BSDF\"]\n",
+                    "{}obj.data.materials.append(mat)\n"
+                ),
+                shape,
+                object.name,
+                object.name,
+                object.material.blender_python()
+            );
+        }
+        let half_width = (self.camera_fov / 2.).tan() / 2.;
+        script += &format!(
+            concat!(
+                "\nbpy.ops.object.camera_add(location={})\n",
+                "camera = bpy.context.active_object\n",
+                "camera.rotation_mode = \"QUATERNION\"\n",
+                "camera.rotation_quaternion = Vector({}).to_track_quat(\"-Z\", \"Y\")\n",
+                "camera.data.angle = {}\n",
+                "bpy.context.scene.camera = camera\n"
+            ),
+            blender::point(self.camera_position),
+            blender::point(self.camera_direction),
+            2. * half_width.atan()
+        );
+        script
+    }
+    pub fn ray_count_estimate(&self, width: u32, height: u32) -> u64 {
+        width as u64 * height as u64 * self.max_samples as u64 * self.depth as u64
+    }
+    fn resolve(options: &RenderOptions, hdr: &[f32], exposure: f32) -> Vec<u8> {
+        hdr.par_chunks(3)
+            .flat_map_iter(|c| {
+                gamma_rgb8(
+                    options
+                        .tone_map
+                        .apply(exposure * Color::new(c[0], c[1], c[2])),
+                )
+            })
+            .collect()
+    }
+    fn in_pool<T: Send>(options: &RenderOptions, render: impl FnOnce() -> T + Send) -> T {
+        match &options.pool {
+            Some(pool) => pool.install(render),
+            None => render(),
+        }
+    }
+    pub fn render_with_options(&self, options: &RenderOptions) -> Vec<u8> {
+        self.render_with_stats(options).0
+    }
+    /// Renders to RGB8 and reports timings and the exposure that was applied.
+    pub fn render_with_stats(&self, options: &RenderOptions) -> (Vec<u8>, RenderStats) {
+        let mut rgb = Vec::new();
+        let stats = self.render_timed(options, &mut ExposureState::default(), |hdr, exposure| {
+            rgb = Self::resolve(options, hdr, exposure)
+        });
+        (rgb, stats)
+    }
+    /// Renders `band_height` rows at a time and hands each band's RGB8 rows to `write`, so peak
+    /// memory is one band. Bands are byte-identical to the same rows of a full render.
+    pub fn render_bands(
+        &self,
+        options: &RenderOptions,
+        band_height: u32,
+        mut write: impl FnMut(&[u8]) -> io::Result<()> + Send,
+    ) -> io::Result<()> {
+        let (Exposure::Manual(exposure), None) = (options.exposure, options.perceptual_stop) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "auto exposure and perceptual stop need the whole film and cannot be used with \
+                 banded rendering",
+            ));
+        };
+        Self::in_pool(options, || {
+            let mut start = 0;
+            while start < options.height {
+                let end = (start + band_height.max(1)).min(options.height);
+                write(&Self::resolve(
+                    options,
+                    &self.render_hdr_rows(options, start..end),
+                    exposure,
+                ))?;
+                start = end;
+            }
+            Ok(())
+        })
+    }
+    pub fn render(&self, buffer: &mut [u32], options: &RenderOptions) -> RenderStats {
+        self.render_smoothed(buffer, options, &mut ExposureState::default())
+    }
+    /// Like [`Scene::render`], but eases auto exposure from the value `exposure` was left at by
+    /// the previous frame instead of jumping to the new metering.
+    pub fn render_smoothed(
+        &self,
+        buffer: &mut [u32],
+        options: &RenderOptions,
+        exposure: &mut ExposureState,
+    ) -> RenderStats {
+        self.render_timed(options, exposure, |hdr, exposure| {
+            buffer
+                .par_iter_mut()
+                .zip(hdr.par_chunks(3))
+                .for_each(|(pixel, c)| {
+                    *pixel = gamma(
+                        options
+                            .tone_map
+                            .apply(exposure * Color::new(c[0], c[1], c[2])),
+                    )
+                });
+        })
+    }
+    /// Renders the whole film at once, or with `perceptual_stop` in passes of `PASS_SAMPLES`
+    /// until the resolved image settles. Returns the film, the samples per pixel and why it
+    /// stopped.
+    fn render_passes(
+        &self,
+        options: &RenderOptions,
+        exposure: &mut ExposureState,
+    ) -> (Vec<f32>, u32, StopReason) {
+        let Some(threshold) = options.perceptual_stop else {
+            return (
+                self.render_hdr(options),
+                options.max_samples,
+                StopReason::Samples,
+            );
+        };
+        let mut stop = PerceptualStop::new(threshold);
+        let mut film = vec![0.; options.width as usize * options.height as usize * 3];
+        let mut taken = 0;
+        for pass in 0u64.. {
+            if taken >= options.max_samples {
+                break;
+            }
+            let samples = PASS_SAMPLES.min(options.max_samples - taken);
+            let hdr = self.render_hdr(&RenderOptions {
+                max_samples: samples,
+                seed: options
+                    .seed
+                    .map(|seed| seed ^ pass.wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+                ..options.clone()
+            });
+            taken += samples;
+            let weight = samples as f32 / taken as f32;
+            film.iter_mut()
+                .zip(&hdr)
+                .for_each(|(film, pass)| *film += (pass - *film) * weight);
+            let resolved = Self::resolve(options, &film, exposure.meter(options.exposure, &film));
+            if stop.check(&resolved, options.width, options.height) {
+                return (film, taken, StopReason::Perceptual);
+            }
+        }
+        (film, taken, StopReason::Samples)
+    }
+    fn render_timed(
+        &self,
+        options: &RenderOptions,
+        exposure: &mut ExposureState,
+        resolve: impl FnOnce(&[f32], f32) + Send,
+    ) -> RenderStats {
+        let (width, height) = (options.width, options.height);
+        let estimate = format_count(self.ray_count_estimate(width, height));
+        log::debug!("rendering {width}x{height}, ~{estimate} rays");
+        #[cfg(feature = "stats")]
+        stats::reset_ray_stats();
+        let mut render_stats = RenderStats::default();
+        #[cfg(feature = "memory-stats")]
+        let mut memory = MemoryStats {
+            scene: alloc::current_bytes(),
+            ..Default::default()
+        };
+        #[cfg(feature = "memory-stats")]
+        alloc::reset_peak();
+        let start = Instant::now();
+        self.shadow_casters();
+        render_stats.preprocess = start.elapsed();
+        #[cfg(feature = "memory-stats")]
+        {
+            memory.preprocess = alloc::current_bytes();
+        }
+        Self::in_pool(options, || {
+            #[cfg(feature = "stats")]
+            rayon::broadcast(|_| stats::discard_shape_counters());
+            let start = Instant::now();
+            let (hdr, samples, stop) = self.render_passes(options, exposure);
+            render_stats.render = start.elapsed();
+            render_stats.samples = samples;
+            render_stats.stop = Some(stop);
+            #[cfg(feature = "stats")]
+            {
+                rayon::broadcast(|_| stats::flush_shape_counters());
+                render_stats.shapes = Some(stats::take_shape_stats());
+            }
+            #[cfg(feature = "memory-stats")]
+            {
+                memory.film = alloc::current_bytes();
+            }
+            let start = Instant::now();
+            let applied = exposure.meter(options.exposure, &hdr);
+            resolve(&hdr, applied);
+            render_stats.resolve = start.elapsed();
+            render_stats.exposure = Some(applied);
+        });
+        #[cfg(feature = "stats")]
+        {
+            render_stats.rays = Some(stats::get_ray_count());
+            render_stats.hits = Some(stats::get_hit_count());
+        }
+        #[cfg(feature = "memory-stats")]
+        {
+            memory.peak = alloc::peak_bytes();
+            render_stats.memory = Some(memory);
+        }
+        log::info!("{render_stats} (at most ~{estimate} rays)");
+        render_stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Dielectric, Light, Metal, ScatterRecord, Sphere};
+
+    fn sphere(
+        name: &str,
+        center: Point,
+        radius: f32,
+        material: Arc<dyn Material + Sync + Send>,
+    ) -> Object {
+        Object {
+            name: name.into(),
+            shape: Arc::new(Sphere {
+                center,
+                radius,
+                velocity: None,
+            }),
+            material,
+            cast_shadow: true,
+        }
+    }
+
+    fn test_scene() -> Scene {
+        Scene::new(World::new(vec![
+            sphere(
+                "ground",
+                Point::new(0., -100.5, -1.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+            ),
+            sphere(
+                "light",
+                Point::new(0., 0.5, -1.2),
+                0.5,
+                Arc::new(Light::new(Color::new(4., 4., 4.))),
+            ),
+            sphere(
+                "glass",
+                Point::new(-1., 0., -1.),
+                0.5,
+                Arc::new(Dielectric::new(1.5)),
+            ),
+            sphere("gold", Point::new(1., 0., -1.), 0.5, Arc::new(Metal::GOLD)),
+        ]))
+    }
+
+    fn seeded(width: u32, height: u32, samples: u32) -> RenderOptions {
+        RenderOptions {
+            width,
+            height,
+            max_samples: samples,
+            depth: 8,
+            seed: Some(7),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sequential_and_parallel_renders_match() {
+        let scene = test_scene();
+        let sequential = RenderOptions {
+            pool: Some(RenderOptions::thread_pool(1).unwrap()),
+            ..seeded(32, 18, 4)
+        };
+        let parallel = RenderOptions {
+            pool: Some(RenderOptions::thread_pool(4).unwrap()),
+            ..seeded(32, 18, 4)
+        };
+        assert_eq!(
+            scene.render_with_options(&sequential),
+            scene.render_with_options(&parallel)
+        );
+    }
+
+    #[test]
+    fn soft_clip_passes_through_below_the_shoulder() {
+        let tone_map = ToneMap::soft_clip(0.8).unwrap();
+        let c = Color::new(0.2, 0.5, 0.8);
+        assert_eq!(tone_map.apply(c), c);
+    }
+
+    #[test]
+    fn soft_clip_keeps_hue_above_the_shoulder() {
+        let tone_map = ToneMap::soft_clip(0.8).unwrap();
+        let c = Color::new(0.6, 0.96, 1.2);
+        let out = tone_map.apply(c);
+        assert!(out.z < 1. && out.z > 0.8);
+        assert!((out.x / out.z - 0.5).abs() < 1e-5);
+        assert!((out.y / out.z - 0.8).abs() < 1e-5);
+        assert!(tone_map.apply(2. * c).z > out.z);
+    }
+
+    #[test]
+    fn soft_clip_rejects_shoulders_outside_the_unit_interval() {
+        for shoulder in [0., 1., -0.5, 2., f32::NAN] {
+            assert!(ToneMap::soft_clip(shoulder).is_err());
+        }
+        assert!("soft-clip:1.5".parse::<ToneMap>().is_err());
+        assert!(matches!(
+            "soft-clip:0.5".parse(),
+            Ok(ToneMap::SoftClip { shoulder: 0.5 })
+        ));
+        let c = ToneMap::SoftClip { shoulder: 1. }.apply(Color::new(3., 2., 1.));
+        assert!(c.x.is_finite() && c.x <= 1.);
+    }
+
+    fn gray_film(luminances: &[f32]) -> Vec<f32> {
+        luminances.iter().flat_map(|&l| [l, l, l]).collect()
+    }
+
+    const AUTO: Exposure = Exposure::Auto {
+        min: 0.01,
+        max: 100.,
+    };
+
+    #[test]
+    fn auto_exposure_maps_the_geometric_mean_to_middle_gray() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4 * b;
+        assert!(close(AUTO.meter(&gray_film(&[0.36; 16])), 0.5));
+        assert!(close(AUTO.meter(&gray_film(&[0.09, 0.36, 0.09, 0.36])), 1.));
+        let mut film = gray_film(&[0.09, 0.36, 0., f32::NAN]);
+        film.extend([0., 0., 0.]);
+        assert!(close(AUTO.meter(&film), 1.));
+        assert_eq!(AUTO.meter(&gray_film(&[0.; 4])), 1.);
+    }
+
+    #[test]
+    fn auto_exposure_respects_its_limits() {
+        let limited = Exposure::Auto { min: 0.5, max: 2. };
+        assert_eq!(limited.meter(&gray_film(&[0.0018; 4])), 2.);
+        assert_eq!(limited.meter(&gray_film(&[18.; 4])), 0.5);
+        assert_eq!(Exposure::Manual(3.).meter(&gray_film(&[18.; 4])), 3.);
+    }
+
+    #[test]
+    fn highlight_exposure_keeps_the_brightest_pixels_below_white() {
+        let mut luminances = vec![0.18; 99];
+        luminances.push(10.);
+        let film = gray_film(&luminances);
+        let highlight = Exposure::Highlight {
+            min: 0.01,
+            max: 100.,
+            percentile: 1.,
+        };
+        let exposure = highlight.meter(&film);
+        assert!(exposure <= 0.1 && exposure < AUTO.meter(&film));
+        let median = Exposure::Highlight {
+            min: 0.01,
+            max: 100.,
+            percentile: 0.5,
+        };
+        assert_eq!(median.meter(&film), AUTO.meter(&film));
+    }
+
+    #[test]
+    fn exposure_state_eases_towards_new_meterings() {
+        let mut state = ExposureState::default();
+        assert!((state.meter(AUTO, &gray_film(&[0.18; 4])) - 1.).abs() < 1e-5);
+        let brighter = gray_film(&[0.045; 4]);
+        let first = state.meter(AUTO, &brighter);
+        assert!(first > 1. && first < 4.);
+        for _ in 0..50 {
+            state.meter(AUTO, &brighter);
+        }
+        assert!((state.value().unwrap() - 4.).abs() < 1e-3);
+        assert_eq!(state.meter(Exposure::Manual(2.), &brighter), 2.);
+    }
+
+    #[test]
+    fn render_records_the_applied_exposure() {
+        let scene = test_scene();
+        let (_, stats) = scene.render_with_stats(&RenderOptions {
+            exposure: AUTO,
+            ..seeded(16, 9, 2)
+        });
+        let exposure = stats.exposure.unwrap();
+        assert!(exposure > 0.01 && exposure < 100.);
+        assert!(stats.to_string().contains("exposure"));
+    }
+
+    #[test]
+    fn perceptual_stop_ends_once_passes_agree() {
+        let mut scene = Scene::new(World::new(Vec::new()));
+        scene.set_background_gradient(Color::new(0.2, 0.4, 0.9), Color::new(1., 1., 1.));
+        let options = RenderOptions {
+            perceptual_stop: Some(0.01),
+            ..seeded(16, 16, 256)
+        };
+        let (_, stats) = scene.render_with_stats(&options);
+        assert_eq!(stats.stop, Some(StopReason::Perceptual));
+        assert_eq!(stats.samples, 3 * PASS_SAMPLES);
+        let (_, stats) = scene.render_with_stats(&RenderOptions {
+            perceptual_stop: Some(0.),
+            ..options.clone()
+        });
+        assert_eq!(stats.stop, Some(StopReason::Samples));
+        assert_eq!(stats.samples, 256);
+        let (_, stats) = scene.render_with_stats(&RenderOptions {
+            perceptual_stop: None,
+            ..options
+        });
+        assert_eq!(
+            (stats.stop, stats.samples),
+            (Some(StopReason::Samples), 256)
+        );
+    }
+
+    #[test]
+    fn banded_render_matches_the_full_render() {
+        let scene = test_scene();
+        let options = seeded(24, 20, 4);
+        let full = scene.render_with_options(&options);
+        for band_height in [1, 7, 20, 64] {
+            let mut banded = Vec::new();
+            scene
+                .render_bands(&options, band_height, |rgb| {
+                    banded.extend_from_slice(rgb);
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(banded, full);
+        }
+        let auto = RenderOptions {
+            exposure: Exposure::Auto { min: 0.1, max: 10. },
+            ..options
+        };
+        assert!(scene.render_bands(&auto, 8, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn memory_check_catches_overflowing_sizes() {
+        let options = RenderOptions {
+            width: 16000,
+            height: 9000,
+            ..Default::default()
+        };
+        assert_eq!(options.memory_estimate(), Some(16000 * 9000 * 19));
+        assert!(options.check_memory(1 << 30).is_err());
+        assert!(options.check_memory(4 << 30).is_ok());
+        let huge = RenderOptions {
+            width: u32::MAX,
+            height: u32::MAX,
+            ..Default::default()
+        };
+        assert_eq!(huge.memory_estimate(), None);
+        assert!(huge.check_memory(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn fov_steps_accumulate_and_clamp() {
+        let aspect = 16. / 9.;
+        let vertical = |camera: &CameraState| vertical_fov(camera.fov, aspect).to_degrees();
+        let mut camera = test_scene().camera_state();
+        camera.fov = fov_from_vertical(60f32.to_radians(), aspect);
+        for _ in 0..3 {
+            camera.step_fov(5., aspect);
+        }
+        assert!((vertical(&camera) - 75.).abs() < 1e-3);
+        camera.step_fov(-5., aspect);
+        assert!((vertical(&camera) - 70.).abs() < 1e-3);
+        for _ in 0..100 {
+            camera.step_fov(5., aspect);
+        }
+        assert!((vertical(&camera) - MAX_FOV).abs() < 1e-3);
+        assert!(camera.fov < PI);
+        for _ in 0..100 {
+            camera.step_fov(-5., aspect);
+        }
+        assert!((vertical(&camera) - MIN_FOV).abs() < 1e-3);
+        camera.step_fov(5., aspect);
+        assert!((vertical(&camera) - 10.).abs() < 1e-3);
+        let mut scene = test_scene();
+        scene.set_camera_state(camera);
+        assert_eq!(scene.camera_fov, camera.fov);
+    }
+
+    #[test]
+    fn coincident_points_are_visible() {
+        let scene = test_scene();
+        let ground = Point::new(0., -0.5, -1.);
+        assert!(scene.visible(ground, ground));
+        assert!(scene.visible(ground, ground + Vector::new(0., 1e-6, 0.)));
+        assert!(!scene.visible(Point::new(0., -1., -1.), Point::new(0., 2., -1.)));
+    }
+
+    #[test]
+    fn debug_pixel_json_names_the_first_object_hit() {
+        let mut scene = Scene::new(World::new(vec![sphere(
+            "ball",
+            Point::new(0., 0., -3.),
+            1.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )]));
+        scene.camera_fov = 60f32.to_radians();
+        let options = seeded(9, 9, 4);
+        let report = scene.trace_pixel_paths(4, 4, &options);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["pixel"], serde_json::json!([4, 4]));
+        let samples = json["samples"].as_array().unwrap();
+        assert_eq!(samples.len(), 4);
+        for sample in samples {
+            let first = &sample["bounces"][0];
+            assert_eq!(first["object"], "ball");
+            assert!((first["t"].as_f64().unwrap() - 2.).abs() < 0.05);
+        }
+        let corner = scene.trace_pixel_paths(0, 0, &options);
+        assert_eq!(corner.samples[0].bounces[0].event, "miss");
+    }
+
+    #[test]
+    fn debug_paths_match_the_rendered_pixel() {
+        let scene = test_scene();
+        let options = seeded(16, 9, 8);
+        let hdr = scene.render_hdr(&options);
+        let report = scene.trace_pixel_paths(5, 6, &options);
+        let index = (6 * 16 + 5) * 3;
+        let mean = report
+            .samples
+            .iter()
+            .fold(Color::ZERO, |sum, sample| sum + sample.color / 8.);
+        assert!((mean.x - hdr[index]).abs() < 1e-5);
+        assert!((mean.y - hdr[index + 1]).abs() < 1e-5);
+        assert!((mean.z - hdr[index + 2]).abs() < 1e-5);
+    }
+
+    #[test]
+    fn override_resolves_built_ins_then_scene_materials() {
+        let mut scene = crate::scene_file::parse_scene(
+            "material red lambertian 0.9 0.1 0.1\n\
+             material lamp light 4 4 4\n\
+             sphere ball red 0 0 -3 1\n\
+             sphere sun lamp 0 3 -3 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            scene.override_material("clay").unwrap().name(),
+            "Lambertian"
+        );
+        assert_eq!(scene.override_material("normal").unwrap().name(), "Normal");
+        let red = scene.override_material("red").unwrap();
+        assert!(Arc::ptr_eq(&red, scene.named_material("red").unwrap()));
+        assert!(scene.override_material("blue").is_err());
+
+        scene.material_override = Some(red.clone());
+        let ball = scene.objects_iter().next().unwrap();
+        assert!(Arc::ptr_eq(scene.material(ball), &red));
+        let sun = scene.objects_iter().nth(1).unwrap();
+        assert!(Arc::ptr_eq(scene.material(sun), &sun.material));
+    }
+
+    fn overlap_warnings(scene: &Scene) -> Vec<String> {
+        scene
+            .validate()
+            .into_iter()
+            .map(|warning| warning.message)
+            .filter(|message| message.contains("overlap") || message.contains("buried"))
+            .collect()
+    }
+
+    #[test]
+    fn validate_flags_duplicate_and_buried_objects() {
+        assert!(overlap_warnings(&test_scene()).is_empty());
+        let gray = || -> Arc<dyn Material + Sync + Send> {
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+        };
+        let scene = Scene::new(World::new(vec![
+            sphere("big", Point::new(0., 0., -5.), 2., gray()),
+            sphere("pebble", Point::new(0.5, 0., -5.), 0.3, gray()),
+            sphere("twin", Point::new(4., 0., -5.), 1., gray()),
+            sphere("twin copy", Point::new(4., 0., -5.), 1., gray()),
+            sphere("neighbour", Point::new(1.5, 1.5, -5.), 1., gray()),
+        ]));
+        let mut warnings = overlap_warnings(&scene);
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                "object pebble is buried inside big",
+                "objects twin and twin copy fully overlap",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_handles_many_objects() {
+        let objects = (0..20_000)
+            .map(|i| {
+                let material: Arc<dyn Material + Sync + Send> =
+                    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+                let (x, z) = ((i % 200) as f32, (i / 200) as f32);
+                sphere(&format!("s{i}"), Point::new(x, 0., -z), 0.4, material)
+            })
+            .collect();
+        let scene = Scene::new(World::new(objects));
+        assert!(overlap_warnings(&scene).is_empty());
+    }
+
+    #[test]
+    fn render_stats_aggregate_fake_durations() {
+        let mut stats = RenderStats {
+            preprocess: Duration::from_millis(10),
+            render: Duration::from_millis(500),
+            resolve: Duration::from_millis(40),
+            rays: Some(1_000_000),
+            hits: Some(600_000),
+            ..Default::default()
+        };
+        assert_eq!(stats.total(), Duration::from_millis(550));
+        assert_eq!(stats.rays_per_second(), Some(2e6));
+        stats.merge(&RenderStats {
+            render: Duration::from_millis(500),
+            rays: Some(1_000_000),
+            ..Default::default()
+        });
+        assert_eq!(stats.total(), Duration::from_millis(1050));
+        assert_eq!(stats.rays, Some(2_000_000));
+        assert_eq!(stats.hits, Some(600_000));
+        assert_eq!(stats.rays_per_second(), Some(2e6));
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["render"], 1.0);
+        assert_eq!(json["rays"], 2_000_000);
+        assert!(json["shapes"].is_null() && json["stop"].is_null());
+    }
+
+    #[test]
+    fn zero_render_time_has_no_ray_rate() {
+        let stats = RenderStats {
+            rays: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(stats.rays_per_second(), None);
+        assert!(stats.to_string().starts_with("0.000s"));
+        assert_eq!(RenderStats::default().rays_per_second(), None);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn sphere_pixels_cost_more_than_background() {
+        let scene = Scene::new(World::new(vec![
+            sphere(
+                "left",
+                Point::new(-1., 0., -1.),
+                0.5,
+                Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            ),
+            sphere("right", Point::new(1., 0., -1.), 0.5, Arc::new(Metal::GOLD)),
+        ]));
+        let (width, height) = (40, 30);
+        let counts = scene.intersection_test_counts(width, height);
+        let (mut covered, mut background) = (Vec::new(), Vec::new());
+        for (index, &count) in counts.iter().enumerate() {
+            let index = index as u32;
+            let ray = scene.camera_ray(
+                (index % width) as f32,
+                (index / width) as f32,
+                width,
+                height,
+            );
+            match scene.world().hit_object(&ray, &scene.render_range()) {
+                Some(_) => covered.push(count),
+                None => background.push(count),
+            }
+        }
+        assert!(!covered.is_empty() && !background.is_empty());
+        let max_background = background.iter().copied().max().unwrap();
+        assert!(covered.iter().all(|&count| count > max_background));
+    }
+
+    fn submerged_scene(inner_ior: Option<f32>) -> Scene {
+        let mut objects = vec![
+            sphere("pool", Point::ZERO, 5., Arc::new(Dielectric::new(1.5))),
+            sphere(
+                "target",
+                Point::new(0., 0., -9.),
+                2.,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.3))),
+            ),
+            sphere(
+                "light",
+                Point::new(0., 8., -6.),
+                2.,
+                Arc::new(Light::new(Color::new(4., 4., 4.))),
+            ),
+        ];
+        if let Some(ior) = inner_ior {
+            objects.push(sphere(
+                "inner",
+                Point::new(0., 0., -2.),
+                0.8,
+                Arc::new(Dielectric::new(ior)),
+            ));
+        }
+        Scene::new(World::new(objects))
+    }
+
+    fn mean_difference(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum::<f32>() / a.len() as f32
+    }
+
+    #[test]
+    fn camera_inside_glass_sees_index_matched_glass_as_invisible() {
+        let options = seeded(24, 16, 64);
+        let render = |scene: Scene| scene.render_hdr_rows(&options, 0..options.height);
+        let reference = render(submerged_scene(None));
+        let matched = mean_difference(&reference, &render(submerged_scene(Some(1.5))));
+        let air_bubble = mean_difference(&reference, &render(submerged_scene(Some(1.))));
+        assert!(matched * 4. < air_bubble);
+    }
+
+    fn motion_at_center(velocity: Option<Vector>, previous_position: Point) -> (f32, f32) {
+        let scene = Scene::new(World::new(vec![Object {
+            name: "ball".into(),
+            shape: Arc::new(Sphere {
+                center: Point::new(0., 0., -5.),
+                radius: 1.,
+                velocity,
+            }),
+            material: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            cast_shadow: true,
+        }]));
+        let previous = CameraState {
+            position: previous_position,
+            ..scene.camera_state()
+        };
+        let (width, height) = (32, 24);
+        let motion = scene.render_motion_vectors(&previous, width, height);
+        let center = ((height / 2) * width + width / 2) as usize;
+        assert!(motion[0].is_nan(), "corner pixels miss the ball");
+        (motion[2 * center], motion[2 * center + 1])
+    }
+
+    #[test]
+    fn motion_vectors_follow_the_camera_and_moving_objects() {
+        // The centre pixel sees (0, 0, -4); seen from half a unit to the right it sits at
+        // NDC x = -focal * 0.5 / 4.
+        let focal = 2. / (3. * PI / 8.).tan();
+        let expected = focal * 0.5 / 4. * 32. / 2.;
+        let (dx, dy) = motion_at_center(None, Point::new(0.5, 0., 0.));
+        assert!((dx - expected).abs() < 1e-3 && dy.abs() < 1e-3, "{dx} {dy}");
+        let (dx, dy) = motion_at_center(Some(Vector::new(0.5, 0., 0.)), Point::ZERO);
+        assert!((dx - expected).abs() < 1e-3 && dy.abs() < 1e-3, "{dx} {dy}");
+        assert_eq!(motion_at_center(None, Point::ZERO), (0., 0.));
+    }
+
+    /// Passes rays straight through and records the bounce index of every hit.
+    struct Recorder(std::sync::Mutex<Vec<u32>>);
+
+    impl Material for Recorder {
+        fn on_hit(&self, ray: &Ray, rec: &Hit) -> Option<ScatterRecord> {
+            self.0.lock().unwrap().push(rec.depth);
+            Some(ScatterRecord {
+                attenuation: Color::WHITE,
+                scattered: Ray {
+                    origin: ray.at(rec.t),
+                    ..*ray
+                },
+                pdf: None,
+            })
+        }
+    }
+
+    #[test]
+    fn hits_record_their_bounce_index() {
+        let recorder = Arc::new(Recorder(Default::default()));
+        let scene = Scene::new(World::new(
+            (1..=3)
+                .map(|i| {
+                    sphere(
+                        "ball",
+                        Point::new(0., 0., -3. * i as f32),
+                        1.,
+                        recorder.clone(),
+                    )
+                })
+                .collect(),
+        ));
+        let ray = scene.camera_ray(16., 12., 32, 24);
+        scene.trace(&ray, &scene.render_range(), 4);
+        assert_eq!(*recorder.0.lock().unwrap(), [0, 1, 2, 3]);
+        recorder.0.lock().unwrap().clear();
+        scene.trace(&ray, &scene.render_range(), scene.depth);
+        assert_eq!(*recorder.0.lock().unwrap(), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn bounce_layers_sum_to_the_beauty_image() {
+        let scene = test_scene();
+        let options = seeded(16, 12, 8);
+        let beauty = scene.render_hdr(&options);
+        let layers = scene.render_bounce_layers(&options);
+        for (i, &value) in beauty.iter().enumerate() {
+            let sum: f32 = layers.iter().map(|layer| layer[i]).sum();
+            assert!(
+                (sum - value).abs() <= 1e-5 * value.max(1.),
+                "{sum} vs {value}"
+            );
+        }
+        assert!(layers.iter().all(|layer| layer.iter().any(|&v| v > 0.)));
+
+        let direct = Scene::new(World::new(vec![sphere(
+            "light",
+            Point::new(0., 0., -3.),
+            1.,
+            Arc::new(Light::new(Color::new(2., 2., 2.))),
+        )]));
+        let [emitted, one, many] = direct.render_bounce_layers(&options);
+        assert_eq!(emitted, direct.render_hdr(&options));
+        assert!(emitted.iter().any(|&v| v > 0.));
+        assert!(one.iter().chain(&many).all(|&v| v == 0.));
+    }
+
+    #[test]
+    fn every_render_option_changes_the_hash() {
+        let base = RenderOptions {
+            width: 64,
+            height: 48,
+            max_samples: 16,
+            depth: 8,
+            ..Default::default()
+        };
+        let variants = [
+            RenderOptions {
+                width: 65,
+                ..base.clone()
+            },
+            RenderOptions {
+                height: 49,
+                ..base.clone()
+            },
+            RenderOptions {
+                max_samples: 17,
+                ..base.clone()
+            },
+            RenderOptions {
+                depth: 9,
+                ..base.clone()
+            },
+            RenderOptions {
+                tone_map: ToneMap::Reinhard,
+                ..base.clone()
+            },
+            RenderOptions {
+                tone_map: ToneMap::SoftClip { shoulder: 0.8 },
+                ..base.clone()
+            },
+            RenderOptions {
+                tone_map: ToneMap::SoftClip { shoulder: 0.7 },
+                ..base.clone()
+            },
+            RenderOptions {
+                exposure: Exposure::Manual(2.),
+                ..base.clone()
+            },
+            RenderOptions {
+                exposure: AUTO,
+                ..base.clone()
+            },
+            RenderOptions {
+                exposure: Exposure::Auto {
+                    min: 0.01,
+                    max: 50.,
+                },
+                ..base.clone()
+            },
+            RenderOptions {
+                exposure: Exposure::Highlight {
+                    min: 0.01,
+                    max: 100.,
+                    percentile: 0.99,
+                },
+                ..base.clone()
+            },
+            RenderOptions {
+                exposure: Exposure::Highlight {
+                    min: 0.01,
+                    max: 100.,
+                    percentile: 0.9,
+                },
+                ..base.clone()
+            },
+            RenderOptions {
+                seed: Some(0),
+                ..base.clone()
+            },
+            RenderOptions {
+                seed: Some(1),
+                ..base.clone()
+            },
+            RenderOptions {
+                perceptual_stop: Some(0.),
+                ..base.clone()
+            },
+            RenderOptions {
+                perceptual_stop: Some(0.01),
+                ..base.clone()
+            },
+        ];
+        let mut hashes: Vec<u64> = variants.iter().map(RenderOptions::content_hash).collect();
+        hashes.push(base.content_hash());
+        let count = hashes.len();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), count);
+        let pooled = RenderOptions {
+            pool: Some(RenderOptions::thread_pool(1).unwrap()),
+            ..base.clone()
+        };
+        assert_eq!(pooled.content_hash(), base.content_hash());
+    }
+
+    #[test]
+    fn scene_hash_ignores_object_order_but_not_parameters() {
+        let scene = test_scene();
+        let mut reversed = test_scene();
+        let mut objects: Vec<Object> = scene.objects_iter().cloned().collect();
+        objects.reverse();
+        reversed.set_world(World::new(objects));
+        assert_eq!(scene.content_hash(), reversed.content_hash());
+
+        let mut moved = test_scene();
+        if let Some(object) = moved.objects_iter_mut().next() {
+            object.shape = Arc::new(Sphere {
+                center: Point::new(0., -100.5, -1.01),
+                radius: 100.,
+                velocity: None,
+            });
+        }
+        let mut recolored = test_scene();
+        if let Some(object) = recolored.objects_iter_mut().next() {
+            object.material = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.81)));
+        }
+        let mut rolled = test_scene();
+        rolled.camera_roll = 0.01;
+        let mut dimmed = test_scene();
+        dimmed.set_light_enabled("light", false);
+        let mut sky = test_scene();
+        sky.set_background_gradient(Color::WHITE, Color::ZERO);
+        for changed in [moved, recolored, rolled, dimmed, sky] {
+            assert_ne!(changed.content_hash(), scene.content_hash());
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn shape_counters_break_down_a_mixed_scene() {
+        use crate::geometry::Triangle;
+        let gray: Arc<dyn Material + Sync + Send> =
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut objects = vec![
+            sphere("ground", Point::new(0., -100.5, -1.), 100., gray.clone()),
+            sphere("ball", Point::new(-0.6, 0., -1.5), 0.4, gray.clone()),
+        ];
+        // A small fan of triangles off to the right of the frame.
+        for i in 0..8 {
+            let angle = i as f32 * PI / 4.;
+            let center = Point::new(0.8, 0.2, -2.);
+            let corner = |a: f32| center + 0.2 * Vector::new(a.cos(), a.sin(), 0.);
+            objects.push(Object {
+                name: "fan".into(),
+                shape: Arc::new(Triangle {
+                    a: center,
+                    b: corner(angle),
+                    c: corner(angle + PI / 4.),
+                }),
+                material: gray.clone(),
+                cast_shadow: true,
+            });
+        }
+        let scene = Scene::new(World::new(objects));
+        let (_, stats) = scene.render_with_stats(&seeded(24, 16, 4));
+        let shapes = stats.shapes.unwrap();
+        let [spheres, triangles, curves] = ShapeType::ALL.map(|shape| shapes[shape as usize]);
+        assert!(spheres.tests > 0 && spheres.hits <= spheres.tests);
+        assert!(triangles.tests > 0 && triangles.hits <= triangles.tests);
+        // Triangles are only tested by rays that pass the fan's bounds, a small part of the view.
+        assert!(triangles.tests < stats.rays.unwrap());
+        assert_eq!(curves.tests, 0);
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(json["shapes"]["triangle"]["tests"], triangles.tests);
+        assert_eq!(json["stop"], "samples");
+    }
+}
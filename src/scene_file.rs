@@ -0,0 +1,476 @@
+use std::{collections::HashMap, fs, sync::Arc};
+
+use crate::{
+    curve::{Curve, Curves},
+    geometry::{
+        Dielectric, GradientBackground, Hittable, Lambertian, LambertianTranslucent, Light,
+        Material, Metal, Object, SolidBackground, Sphere, Triangle, Vector, World,
+    },
+    scene::Scene,
+};
+
+fn exactly<const N: usize>(values: &[f32]) -> Result<[f32; N], String> {
+    values
+        .try_into()
+        .map_err(|_| format!("expected {N} numbers, got {}", values.len()))
+}
+
+fn parse_numbers(args: &[&str]) -> Result<Vec<f32>, String> {
+    args.iter()
+        .map(|arg| match arg.parse::<f32>() {
+            Ok(x) if x.is_finite() => Ok(x),
+            Ok(_) => Err(format!("{arg:?} is not a finite number")),
+            Err(_) => Err(format!("{arg:?} is not a number")),
+        })
+        .collect()
+}
+
+fn numbers<const N: usize>(args: &[&str]) -> Result<[f32; N], String> {
+    exactly(&parse_numbers(args)?)
+}
+
+fn vector(v: &[f32]) -> Vector {
+    Vector::new(v[0], v[1], v[2])
+}
+
+pub fn material(kind: &str, args: &[f32]) -> Result<Arc<dyn Material + Sync + Send>, String> {
+    Ok(match kind {
+        "lambertian" => Arc::new(Lambertian::new(vector(&exactly::<3>(args)?))),
+        "translucent" => {
+            let v = exactly::<4>(args)?;
+            Arc::new(LambertianTranslucent::new(vector(&v), v[3]))
+        }
+        "metal" => {
+            let v = exactly::<4>(args)?;
+            Arc::new(Metal::new(vector(&v), v[3]))
+        }
+        "dielectric" => Arc::new(Dielectric::new(exactly::<1>(args)?[0])),
+        "light" => Arc::new(Light::new(vector(&exactly::<3>(args)?))),
+        _ => return Err(format!("unknown material type {kind:?}")),
+    })
+}
+
+/// Bounds on what a scene file may ask for, so a malformed or hostile file fails to load
+/// instead of exhausting memory or rendering forever.
+#[derive(Clone, Copy, Debug)]
+pub struct SceneLimits {
+    pub max_bytes: u64,
+    pub max_line_length: usize,
+    pub max_name_length: usize,
+    pub max_objects: usize,
+    pub max_materials: usize,
+    pub max_samples: u32,
+    pub max_depth: u32,
+}
+
+impl Default for SceneLimits {
+    fn default() -> Self {
+        SceneLimits {
+            max_bytes: 64 << 20,
+            max_line_length: 4096,
+            max_name_length: 256,
+            max_objects: 1_000_000,
+            max_materials: 10_000,
+            max_samples: 1 << 16,
+            max_depth: 1024,
+        }
+    }
+}
+
+/// Pieces each scene-file curve is split into before going into a `Curves` hierarchy.
+const CURVE_SEGMENTS: usize = 2;
+
+/// A positive count of at most `max`, for `samples` and `depth` here and on the command line.
+pub fn count(n: &str, what: &str, max: u32) -> Result<u32, String> {
+    match n.parse() {
+        Ok(0) | Err(_) => Err(format!("bad {what} {n:?}")),
+        Ok(n) if n > max => Err(format!("{what} {n} exceeds the limit of {max}")),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Parses the line-based scene format:
+///
+/// ```text
+/// units <scale>
+/// epsilon <minimum hit distance>
+/// camera <px py pz> <dx dy dz> <ux uy uz> <fov degrees>
+/// roll <degrees>
+/// samples <n>
+/// depth <n>
+/// background solid <r g b> | background sky
+/// background gradient <top r g b> <bottom r g b> [<up x y z> [<horizon> <sharpness>]]
+/// material <name> lambertian <r g b> | translucent <r g b transmittance> | metal <r g b fuzz>
+///     | metal <r g b> roughness <0..1> | dielectric <ior> | light <r g b>
+/// sphere <name> <material> <cx cy cz> <radius>
+/// triangle <name> <material> <ax ay az> <bx by bz> <cx cy cz>
+/// curve <name> <material> <four Bézier control points> <start width> <end width>
+/// ```
+///
+/// Curves that share a name and material become one `Curves` shape. Blank lines and `#`
+/// comments are ignored. Numbers must be finite. Positions, radii and
+/// `epsilon` are multiplied by `units` (e.g. 0.001 for a model in millimetres), which must come
+/// before any `camera`, `sphere`, `triangle` or `curve` line.
+pub fn parse_scene(text: &str) -> Result<Scene, String> {
+    parse_scene_with_limits(text, &SceneLimits::default())
+}
+
+pub fn parse_scene_with_limits(text: &str, limits: &SceneLimits) -> Result<Scene, String> {
+    if text.len() as u64 > limits.max_bytes {
+        return Err(format!(
+            "scene is {} bytes, over the limit of {}",
+            text.len(),
+            limits.max_bytes
+        ));
+    }
+    let mut scene = Scene::new(World::default());
+    let mut materials: HashMap<&str, Arc<dyn Material + Sync + Send>> = HashMap::new();
+    let mut objects = Vec::new();
+    let mut curves: Vec<(&str, Arc<dyn Material + Sync + Send>, Vec<Curve>)> = Vec::new();
+    let mut curve_groups: HashMap<(&str, *const ()), usize> = HashMap::new();
+    let mut placed_count = 0;
+    let mut units = 1.;
+    let mut placed = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = words.split_first() else {
+            continue;
+        };
+        let mut parse_line = || -> Result<(), String> {
+            if line.len() > limits.max_line_length {
+                return Err(format!(
+                    "line is {} bytes, over the limit of {}",
+                    line.len(),
+                    limits.max_line_length
+                ));
+            }
+            if let ("material" | "sphere" | "triangle" | "curve", [name, ..]) = (keyword, args) {
+                if name.len() > limits.max_name_length {
+                    return Err(format!(
+                        "name {}... is longer than {} bytes",
+                        &name[..name.floor_char_boundary(16)],
+                        limits.max_name_length
+                    ));
+                }
+            }
+            match (keyword, args) {
+                ("units", args) => {
+                    if placed {
+                        return Err("units must come before the camera and geometry".into());
+                    }
+                    units = numbers::<1>(args)?[0];
+                    if units <= 0. {
+                        return Err(format!("units scale {units} is not positive"));
+                    }
+                }
+                ("epsilon", args) => {
+                    scene.ray_epsilon = Some(units * numbers::<1>(args)?[0].max(0.))
+                }
+                ("camera", args) => {
+                    placed = true;
+                    let v = numbers::<10>(args)?;
+                    scene.camera_position = units * vector(&v[0..3]);
+                    scene.camera_direction = vector(&v[3..6]);
+                    scene.camera_up = vector(&v[6..9]);
+                    scene.camera_fov = v[9].to_radians();
+                }
+                ("roll", args) => scene.camera_roll = numbers::<1>(args)?[0].to_radians(),
+                ("samples", [n]) => {
+                    scene.max_samples = count(n, "sample count", limits.max_samples)?
+                }
+                ("depth", [n]) => scene.depth = count(n, "depth", limits.max_depth)?,
+                ("background", ["solid", args @ ..]) => {
+                    scene.background = Arc::new(SolidBackground {
+                        color: vector(&numbers::<3>(args)?),
+                    })
+                }
+                ("background", ["gradient", args @ ..]) => {
+                    let v = parse_numbers(args)?;
+                    let mut gradient = match v.len() {
+                        6 | 9 | 11 => GradientBackground::new(vector(&v[0..3]), vector(&v[3..6])),
+                        n => return Err(format!("expected 6, 9 or 11 numbers, got {n}")),
+                    };
+                    if v.len() >= 9 {
+                        gradient = gradient.with_up(vector(&v[6..9]));
+                    }
+                    if v.len() == 11 {
+                        gradient = gradient.with_horizon(v[9], v[10]);
+                    }
+                    scene.background = Arc::new(gradient)
+                }
+                ("background", ["sky"]) => {
+                    scene.background = Arc::new(GradientBackground::sky_blue())
+                }
+                ("material", [name, kind, args @ ..]) => {
+                    if materials.len() >= limits.max_materials && !materials.contains_key(name) {
+                        return Err(format!("more than {} materials", limits.max_materials));
+                    }
+                    let material = match (*kind, args) {
+                        ("metal", [color @ .., "roughness", roughness]) => {
+                            let [r, g, b] = numbers::<3>(color).map_err(|_| {
+                                "metal takes either a fuzz or a roughness, not both".to_string()
+                            })?;
+                            let roughness = numbers::<1>(&[roughness])?[0];
+                            if !(0. ..=1.).contains(&roughness) {
+                                return Err(format!(
+                                    "roughness {roughness} is not between 0 and 1"
+                                ));
+                            }
+                            Arc::new(Metal::new_with_roughness(Vector::new(r, g, b), roughness))
+                        }
+                        _ => material(kind, &parse_numbers(args)?)?,
+                    };
+                    materials.insert(*name, material);
+                }
+                ("sphere" | "triangle" | "curve", [name, material, args @ ..]) => {
+                    if placed_count >= limits.max_objects {
+                        return Err(format!("more than {} objects", limits.max_objects));
+                    }
+                    placed = true;
+                    placed_count += 1;
+                    let material = materials
+                        .get(*material)
+                        .ok_or(format!("unknown material {material:?}"))?
+                        .clone();
+                    if keyword == "curve" {
+                        let v = numbers::<14>(args)?;
+                        if v[12] < 0. || v[13] < 0. {
+                            return Err("curve widths must not be negative".into());
+                        }
+                        let curve = Curve::new(
+                            [0, 3, 6, 9].map(|i| units * vector(&v[i..i + 3])),
+                            units * v[12],
+                            units * v[13],
+                        );
+                        let key = (*name, Arc::as_ptr(&material) as *const ());
+                        match curve_groups.get(&key) {
+                            Some(&i) => curves[i].2.push(curve),
+                            None => {
+                                curve_groups.insert(key, curves.len());
+                                curves.push((name, material, vec![curve]));
+                            }
+                        }
+                        return Ok(());
+                    }
+                    let shape: Arc<dyn Hittable + Sync + Send> = if keyword == "sphere" {
+                        let v = numbers::<4>(args)?;
+                        if v[3] <= 0. {
+                            return Err(format!("sphere radius {} is not positive", v[3]));
+                        }
+                        Arc::new(Sphere {
+                            center: units * vector(&v[0..3]),
+                            radius: units * v[3],
+                            velocity: None,
+                        })
+                    } else {
+                        let v = numbers::<9>(args)?;
+                        Arc::new(Triangle {
+                            a: units * vector(&v[0..3]),
+                            b: units * vector(&v[3..6]),
+                            c: units * vector(&v[6..9]),
+                        })
+                    };
+                    objects.push(Object {
+                        name: name.to_string(),
+                        shape,
+                        material,
+                        cast_shadow: true,
+                    });
+                }
+                _ => return Err(format!("cannot parse {line:?}")),
+            }
+            Ok(())
+        };
+        parse_line().map_err(|e| format!("line {}: {e}", number + 1))?;
+    }
+    for (name, material, group) in curves {
+        objects.push(Object {
+            name: name.into(),
+            shape: Arc::new(Curves::new(&group, CURVE_SEGMENTS)),
+            material,
+            cast_shadow: true,
+        });
+    }
+    scene.set_world(World::new(objects));
+    for (name, material) in materials {
+        scene.add_material(name, material);
+    }
+    Ok(scene)
+}
+
+pub fn load_scene(path: &str) -> Result<Scene, String> {
+    load_scene_with_limits(path, &SceneLimits::default())
+}
+
+pub fn load_scene_with_limits(path: &str, limits: &SceneLimits) -> Result<Scene, String> {
+    let size = fs::metadata(path)
+        .map_err(|e| format!("{path}: {e}"))?
+        .len();
+    if size > limits.max_bytes {
+        return Err(format!(
+            "{path}: scene is {size} bytes, over the limit of {}",
+            limits.max_bytes
+        ));
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    parse_scene_with_limits(&text, limits).map_err(|e| format!("{path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS: [&str; 2] = [
+        include_str!("../fuzz/corpus/parse_scene/box.scene"),
+        include_str!("../fuzz/corpus/parse_scene/spheres.scene"),
+    ];
+
+    fn error(text: &str, limits: &SceneLimits) -> String {
+        match parse_scene_with_limits(text, limits) {
+            Ok(_) => panic!("{text:?} parsed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn limit_violations_are_reported() {
+        let limits = SceneLimits {
+            max_bytes: 400,
+            max_line_length: 80,
+            max_name_length: 8,
+            max_objects: 2,
+            max_materials: 1,
+            max_samples: 64,
+            max_depth: 16,
+        };
+        let spheres = "material m lambertian 1 1 1\nsphere a m 0 0 0 1\nsphere b m 0 0 0 1\n";
+        assert!(parse_scene_with_limits(spheres, &limits).is_ok());
+        let cases = [
+            (
+                format!("{spheres}sphere c m 0 0 0 1"),
+                "line 4: more than 2 objects",
+            ),
+            (
+                "material m lambertian 1 1 1\nmaterial n light 1 1 1".into(),
+                "line 2: more than 1 materials",
+            ),
+            (
+                "samples 65".into(),
+                "line 1: sample count 65 exceeds the limit of 64",
+            ),
+            ("depth 0".into(), "line 1: bad depth \"0\""),
+            (
+                format!("roll {}", "0".repeat(90)),
+                "line 1: line is 95 bytes",
+            ),
+            (
+                "material verylongname light 1 1 1".into(),
+                "line 1: name verylongname...",
+            ),
+            ("#".repeat(401), "scene is 401 bytes"),
+        ];
+        for (text, expected) in cases {
+            let e = error(&text, &limits);
+            assert!(
+                e.starts_with(expected),
+                "{e:?} should start with {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_an_error_not_a_panic() {
+        let limits = SceneLimits {
+            max_name_length: 4,
+            ..Default::default()
+        };
+        // Used to slice through the middle of a multi-byte character.
+        assert!(error("material ééééé light 1 1 1", &limits).contains("longer than 4 bytes"));
+        for (text, expected) in [
+            ("sphere s m 0 0 0 1", "unknown material \"m\""),
+            ("material m light 1 1", "expected 3 numbers, got 2"),
+            ("material m light nan 1 1", "\"nan\" is not a finite number"),
+            (
+                "camera 0 0 0 0 0 -1 0 1 0 inf",
+                "\"inf\" is not a finite number",
+            ),
+            ("camera", "expected 10 numbers, got 0"),
+            (
+                "background gradient 1 1",
+                "expected 6, 9 or 11 numbers, got 2",
+            ),
+            ("material m metal", "expected 4 numbers, got 0"),
+            (
+                "material m metal 1 1 1 0.2 roughness 0.5",
+                "either a fuzz or a roughness",
+            ),
+            (
+                "material m metal 1 1 1 roughness 1.5",
+                "roughness 1.5 is not between 0 and 1",
+            ),
+            (
+                "material m metal 1 1 1 roughness",
+                "\"roughness\" is not a number",
+            ),
+            ("material m glass 1.5", "unknown material type \"glass\""),
+            (
+                "camera 0 0 0 0 0 -1 0 1 0 45\nunits 2",
+                "line 2: units must come before",
+            ),
+            ("units 0", "units scale 0 is not positive"),
+            (
+                "material m light 1 1 1\nsphere s m 0 0 0 -1",
+                "line 2: sphere radius -1",
+            ),
+            ("samples 99999999999999999999", "bad sample count"),
+            ("sphere", "cannot parse \"sphere\""),
+        ] {
+            let e = error(text, &SceneLimits::default());
+            assert!(e.contains(expected), "{e:?} should mention {expected:?}");
+        }
+    }
+
+    #[test]
+    fn metal_takes_a_roughness_instead_of_a_fuzz() {
+        let scene = parse_scene("material m metal 0.8 0.8 0.8 roughness 0.5").unwrap();
+        let python = scene.named_material("m").unwrap().blender_python();
+        // Blender's roughness is the square root of the fuzz that `roughness 0.5` stores.
+        assert!(python.contains("[\"Roughness\"].default_value = 0.5\n"));
+    }
+
+    #[test]
+    fn corpus_parses_and_its_truncations_never_panic() {
+        for text in CORPUS {
+            let scene = parse_scene(text).unwrap();
+            assert!(scene.objects_iter().count() > 0);
+            for end in (0..text.len()).filter(|&end| text.is_char_boundary(end)) {
+                let _ = parse_scene(&text[..end]);
+            }
+        }
+    }
+
+    #[test]
+    fn curves_sharing_a_name_and_material_become_one_shape() {
+        let strand = |x: f32| format!("{x} 0 -2 {x} 0.1 -2 {x} 0.2 -2 {x} 0.3 -2 0.01 0.005");
+        let text = [
+            "material green lambertian 0.2 0.6 0.2".into(),
+            "material brown lambertian 0.4 0.3 0.1".into(),
+            format!("curve grass green {}", strand(0.)),
+            format!("curve grass green {}", strand(0.1)),
+            format!("curve grass brown {}", strand(0.2)),
+            format!("curve weeds green {}", strand(0.3)),
+            format!("curve grass green {}", strand(0.4)),
+            "sphere ball green 0 0 -5 1".into(),
+        ]
+        .join("\n");
+        let scene = parse_scene(&text).unwrap();
+        let names: Vec<&str> = scene.objects_iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["ball", "grass", "grass", "weeds"]);
+        let limits = SceneLimits {
+            max_objects: 4,
+            ..Default::default()
+        };
+        assert!(error(&text, &limits).starts_with("line 7: more than 4 objects"));
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::{
+    geometry::{Object, World},
+    transform::{Mat4, Transformed},
+};
+
+pub struct SceneNode {
+    pub object: Option<Object>,
+    pub children: Vec<SceneNode>,
+    pub local_transform: Mat4,
+}
+
+impl SceneNode {
+    fn flatten_into(&self, parent: Mat4, objects: &mut Vec<(Object, Mat4)>) {
+        let transform = parent * self.local_transform;
+        if let Some(object) = &self.object {
+            objects.push((object.clone(), transform));
+        }
+        for child in self.children.iter() {
+            child.flatten_into(transform, objects);
+        }
+    }
+}
+
+pub struct SceneGraph {
+    pub root: SceneNode,
+}
+
+impl SceneGraph {
+    pub fn flatten(&self) -> Vec<(Object, Mat4)> {
+        let mut objects = Vec::new();
+        self.root.flatten_into(Mat4::IDENTITY, &mut objects);
+        objects
+    }
+    pub fn to_world(&self) -> World {
+        World::new(
+            self.flatten()
+                .into_iter()
+                .map(|(object, transform)| Object {
+                    shape: Arc::new(Transformed::new(object.shape, transform)),
+                    ..object
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Hittable, Interval, Lambertian, Point, Ray, Sphere, Vector};
+
+    fn node(name: Option<&str>, local_transform: Mat4, children: Vec<SceneNode>) -> SceneNode {
+        SceneNode {
+            object: name.map(|name| Object {
+                name: name.into(),
+                shape: Arc::new(Sphere {
+                    center: Point::ZERO,
+                    radius: 0.5,
+                    velocity: None,
+                }),
+                material: Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+                cast_shadow: true,
+            }),
+            children,
+            local_transform,
+        }
+    }
+
+    #[test]
+    fn children_compose_their_parents_transforms() {
+        let move_right = Mat4::translation(Vector::new(2., 0., 0.));
+        let double = Mat4::scale(Vector::new(2., 2., 2.));
+        let move_back = Mat4::translation(Vector::new(0., 0., -3.));
+        let graph = SceneGraph {
+            root: node(
+                Some("root"),
+                move_right,
+                vec![node(
+                    Some("child"),
+                    double,
+                    vec![node(Some("grandchild"), move_back, vec![])],
+                )],
+            ),
+        };
+        let flat = graph.flatten();
+        let names: Vec<&str> = flat.iter().map(|(o, _)| o.name.as_str()).collect();
+        assert_eq!(names, ["root", "child", "grandchild"]);
+        assert_eq!(flat[1].1, move_right * double);
+        assert_eq!(flat[2].1, move_right * double * move_back);
+        // Local transforms apply first: back 3, doubled to 6, then right 2.
+        let p = flat[2].1.transform_point(Point::ZERO);
+        assert!((p - Point::new(2., 0., -6.)).length() < 1e-6);
+    }
+
+    #[test]
+    fn empty_nodes_still_pass_their_transform_down() {
+        let lift = Mat4::translation(Vector::new(0., 1., 0.));
+        let back = Mat4::translation(Vector::new(0., 0., -4.));
+        let graph = SceneGraph {
+            root: node(
+                None,
+                lift,
+                vec![node(
+                    None,
+                    back,
+                    vec![node(Some("ball"), Mat4::IDENTITY, vec![])],
+                )],
+            ),
+        };
+        let flat = graph.flatten();
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].1, lift * back);
+        let world = graph.to_world();
+        let ray = Ray::new(Point::ZERO, Vector::new(0., 1., -4.).normalize());
+        let hit = world.hit(&ray, &Interval::RENDER_RANGE).unwrap();
+        assert!((hit.t - (17f32.sqrt() - 0.5)).abs() < 1e-4);
+    }
+}
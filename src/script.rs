@@ -0,0 +1,311 @@
+use std::{cell::RefCell, collections::HashMap, fs, rc::Rc, sync::Arc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rhai::{Array, Engine, EvalAltResult, FLOAT, INT};
+
+use crate::{
+    geometry::{Hittable, Material, Object, Point, Sphere, Triangle, Vector, World},
+    scene::Scene,
+    scene_file::{material, SceneLimits},
+};
+
+/// Engine limits, so a runaway script fails to load instead of hanging or exhausting memory.
+const MAX_OPERATIONS: u64 = 50_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_ARRAY_SIZE: usize = 1 << 20;
+const MAX_STRING_SIZE: usize = 1 << 20;
+
+struct Builder {
+    scene: Scene,
+    materials: HashMap<String, Arc<dyn Material + Sync + Send>>,
+    objects: Vec<Object>,
+    rng: StdRng,
+    limits: SceneLimits,
+}
+
+impl Builder {
+    fn add(
+        &mut self,
+        name: &str,
+        material: &str,
+        shape: Arc<dyn Hittable + Sync + Send>,
+    ) -> Result<(), Box<EvalAltResult>> {
+        if self.objects.len() >= self.limits.max_objects {
+            return Err(format!("more than {} objects", self.limits.max_objects).into());
+        }
+        let material = self
+            .materials
+            .get(material)
+            .ok_or(format!("unknown material {material:?}"))?
+            .clone();
+        self.objects.push(Object {
+            name: name.into(),
+            shape,
+            material,
+            cast_shadow: true,
+        });
+        Ok(())
+    }
+}
+
+fn vec3(x: FLOAT, y: FLOAT, z: FLOAT) -> Vector {
+    Vector::new(x as f32, y as f32, z as f32)
+}
+
+fn finite(v: Vector, what: &str) -> Result<Vector, Box<EvalAltResult>> {
+    if [v.x, v.y, v.z].iter().all(|c| c.is_finite()) {
+        Ok(v)
+    } else {
+        Err(format!("{what} {v:?} is not finite").into())
+    }
+}
+
+fn register_vector(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Vector>("Vector")
+        .register_fn("vec3", vec3)
+        .register_get("x", |v: &mut Vector| v.x as FLOAT)
+        .register_get("y", |v: &mut Vector| v.y as FLOAT)
+        .register_get("z", |v: &mut Vector| v.z as FLOAT)
+        .register_fn("+", |a: Vector, b: Vector| a + b)
+        .register_fn("-", |a: Vector, b: Vector| a - b)
+        .register_fn("*", |a: Vector, k: FLOAT| a * k as f32)
+        .register_fn("*", |k: FLOAT, a: Vector| k as f32 * a)
+        .register_fn("/", |a: Vector, k: FLOAT| a / k as f32)
+        .register_fn("dot", |a: Vector, b: Vector| a.dot(b) as FLOAT)
+        .register_fn("cross", |a: Vector, b: Vector| a.cross(b))
+        .register_fn("length", |a: Vector| a.length() as FLOAT)
+        .register_fn("normalize", |a: Vector| a.normalize())
+        .register_fn("to_string", |a: &mut Vector| format!("{a:?}"));
+}
+
+/// Runs a Rhai script that builds a scene through `add_material`, `add_sphere`, `add_quad`,
+/// `set_camera`, `set_samples`, `set_depth`, `seed` and `rand`. `rand` draws from an RNG seeded
+/// by `seed` (0 unless the script calls it), so a script always builds the same scene.
+pub fn run_script(source: &str) -> Result<Scene, String> {
+    let builder = Rc::new(RefCell::new(Builder {
+        scene: Scene::new(World::default()),
+        materials: HashMap::new(),
+        objects: Vec::new(),
+        rng: StdRng::seed_from_u64(0),
+        limits: SceneLimits::default(),
+    }));
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_string_size(MAX_STRING_SIZE);
+    register_vector(&mut engine);
+
+    let b = builder.clone();
+    engine.register_fn(
+        "add_material",
+        move |name: &str, kind: &str, params: Array| -> Result<(), Box<EvalAltResult>> {
+            let params = params
+                .into_iter()
+                .map(|p| {
+                    p.as_float()
+                        .or_else(|_| p.as_int().map(|i| i as FLOAT))
+                        .map(|f| f as f32)
+                        .map_err(|t| format!("material parameter must be a number, not {t}"))
+                })
+                .collect::<Result<Vec<f32>, String>>()?;
+            let material = material(kind, &params)?;
+            let mut b = b.borrow_mut();
+            if b.materials.len() >= b.limits.max_materials && !b.materials.contains_key(name) {
+                return Err(format!("more than {} materials", b.limits.max_materials).into());
+            }
+            b.materials.insert(name.into(), material);
+            Ok(())
+        },
+    );
+    let b = builder.clone();
+    engine.register_fn(
+        "add_sphere",
+        move |name: &str, material: &str, center: Vector, radius: FLOAT| {
+            let radius = radius as f32;
+            if !(radius > 0. && radius.is_finite()) {
+                return Err(format!("sphere radius {radius} is not positive").into());
+            }
+            b.borrow_mut().add(
+                name,
+                material,
+                Arc::new(Sphere {
+                    center: finite(center, "sphere center")?,
+                    radius,
+                    velocity: None,
+                }),
+            )
+        },
+    );
+    let b = builder.clone();
+    engine.register_fn(
+        "add_quad",
+        move |name: &str, material: &str, corner: Point, u: Vector, v: Vector| {
+            let (corner, u, v) = (
+                finite(corner, "quad corner")?,
+                finite(u, "quad edge")?,
+                finite(v, "quad edge")?,
+            );
+            let mut b = b.borrow_mut();
+            b.add(
+                name,
+                material,
+                Arc::new(Triangle {
+                    a: corner,
+                    b: corner + u,
+                    c: corner + u + v,
+                }),
+            )?;
+            b.add(
+                name,
+                material,
+                Arc::new(Triangle {
+                    a: corner,
+                    b: corner + u + v,
+                    c: corner + v,
+                }),
+            )
+        },
+    );
+    let b = builder.clone();
+    engine.register_fn(
+        "set_camera",
+        move |position: Point,
+              direction: Vector,
+              up: Vector,
+              fov: FLOAT|
+              -> Result<(), Box<EvalAltResult>> {
+            let fov = fov as f32;
+            if !(fov > 0. && fov < 180.) {
+                return Err(format!("camera fov {fov} is not between 0 and 180 degrees").into());
+            }
+            let scene = &mut b.borrow_mut().scene;
+            scene.camera_position = finite(position, "camera position")?;
+            scene.camera_direction = finite(direction, "camera direction")?;
+            scene.camera_up = finite(up, "camera up")?;
+            scene.camera_fov = fov.to_radians();
+            Ok(())
+        },
+    );
+    let b = builder.clone();
+    engine.register_fn("set_samples", move |n: INT| {
+        let mut b = b.borrow_mut();
+        b.scene.max_samples = n.clamp(1, b.limits.max_samples as INT) as u32;
+    });
+    let b = builder.clone();
+    engine.register_fn("set_depth", move |n: INT| {
+        let mut b = b.borrow_mut();
+        b.scene.depth = n.clamp(1, b.limits.max_depth as INT) as u32;
+    });
+    let b = builder.clone();
+    engine.register_fn("seed", move |seed: INT| {
+        b.borrow_mut().rng = StdRng::seed_from_u64(seed as u64);
+    });
+    let b = builder.clone();
+    engine.register_fn("rand", move || b.borrow_mut().rng.gen::<FLOAT>());
+
+    engine
+        .run(source)
+        .map_err(|e: Box<EvalAltResult>| e.to_string())?;
+    drop(engine);
+    let Builder {
+        mut scene,
+        materials,
+        objects,
+        ..
+    } = Rc::try_unwrap(builder)
+        .map_err(|_| "script builder is still shared".to_string())?
+        .into_inner();
+    scene.set_world(World::new(objects));
+    for (name, material) in materials {
+        scene.add_material(&name, material);
+    }
+    Ok(scene)
+}
+
+pub fn load_script(path: &str) -> Result<Scene, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    run_script(&source).map_err(|e| format!("{path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Color, Hit};
+
+    #[test]
+    fn tiny_script_builds_its_objects_and_materials() {
+        let scene = run_script(
+            r#"
+            add_material("red", "lambertian", [0.9, 0.1, 0.2]);
+            for i in 0..3 {
+                add_sphere("ball" + i, "red", vec3(i * 2.0, 0.0, -5.0), 0.5);
+            }
+            add_quad("floor", "red", vec3(-5.0, -1.0, -10.0), vec3(10.0, 0.0, 0.0), vec3(0.0, 0.0, 10.0));
+            set_samples(1000000000);
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scene.objects_iter().count(), 5);
+        assert_eq!(scene.max_samples, SceneLimits::default().max_samples);
+        let hit = Hit {
+            t: 1.,
+            normal: Vector::new(0., 1., 0.),
+            is_front: true,
+            object_id: 0,
+            depth: 0,
+            outside_ior: 1.,
+        };
+        let red = scene.named_material("red").unwrap();
+        assert_eq!(red.albedo(&hit), Color::new(0.9, 0.1, 0.2));
+    }
+
+    #[test]
+    fn runaway_scripts_fail_instead_of_hanging() {
+        assert!(run_script("loop { }").is_err());
+        assert!(run_script("fn f(n) { f(n + 1) } f(0);").is_err());
+        assert!(run_script("let a = []; a.pad(2000000, 0);").is_err());
+        let Err(error) = run_script("add_sphere(\"s\", \"missing\", vec3(0.0, 0.0, 0.0), 1.0);")
+        else {
+            panic!("unknown material accepted");
+        };
+        assert!(error.contains("line 1"), "{error}");
+    }
+
+    #[test]
+    fn bad_shapes_and_cameras_are_script_errors() {
+        let material = "add_material(\"m\", \"lambertian\", [0.5, 0.5, 0.5]);\n";
+        for (line, expected) in [
+            ("add_sphere(\"s\", \"m\", vec3(0.0, 0.0, 0.0), 0.0);", "radius 0 is not"),
+            ("add_sphere(\"s\", \"m\", vec3(0.0, 0.0, 0.0), -1.0);", "radius -1 is not"),
+            ("add_sphere(\"s\", \"m\", vec3(0.0, 0.0, 0.0), 0.0 / 0.0);", "radius NaN is not"),
+            (
+                "add_sphere(\"s\", \"m\", vec3(1.0 / 0.0, 0.0, 0.0), 1.0);",
+                "sphere center",
+            ),
+            (
+                "add_quad(\"q\", \"m\", vec3(0.0, 0.0, 0.0), vec3(0.0 / 0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0));",
+                "quad edge",
+            ),
+            (
+                "set_camera(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0), 180.0);",
+                "camera fov 180 is not",
+            ),
+            (
+                "set_camera(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0), 0.0 / 0.0);",
+                "camera fov NaN is not",
+            ),
+            (
+                "set_camera(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0 / 0.0), vec3(0.0, 1.0, 0.0), 60.0);",
+                "camera direction",
+            ),
+        ] {
+            let Err(error) = run_script(&format!("{material}{line}")) else {
+                panic!("{line} accepted");
+            };
+            assert!(error.contains(expected), "{error:?} should mention {expected:?}");
+        }
+    }
+}
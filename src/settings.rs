@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::scene::{CameraState, Exposure, ToneMap};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window_width: u32,
+    pub window_height: u32,
+    pub window_position: Option<(i32, i32)>,
+    pub material_override: Option<String>,
+    /// Camera bookmarks by scene, then by slot.
+    pub bookmarks: BTreeMap<String, BTreeMap<String, CameraState>>,
+    /// Scene file opened when none is given on the command line.
+    pub last_scene: Option<String>,
+    pub tone_map: ToneMap,
+    pub exposure: Exposure,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_width: 800,
+            window_height: 450,
+            window_position: None,
+            material_override: None,
+            bookmarks: BTreeMap::new(),
+            last_scene: None,
+            tone_map: ToneMap::default(),
+            exposure: Exposure::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn store_bookmark(&mut self, scene: &str, slot: u32, camera: CameraState) {
+        self.bookmarks
+            .entry(scene.into())
+            .or_default()
+            .insert(slot.to_string(), camera);
+    }
+    pub fn bookmark(&self, scene: &str, slot: u32) -> Option<CameraState> {
+        self.bookmarks.get(scene)?.get(&slot.to_string()).copied()
+    }
+    pub fn path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "tracer").map(|dirs| dirs.config_dir().join("settings.toml"))
+    }
+    pub fn load() -> Self {
+        Settings::path()
+            .map(|path| Settings::load_from(&path))
+            .unwrap_or_default()
+    }
+    /// Reads `path`, falling back to the defaults if it is missing or can't be parsed.
+    pub fn load_from(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                log::warn!("{}: {e}; using default settings", path.display());
+                Settings::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                log::warn!("{}: {e}; using default settings", path.display());
+                Settings::default()
+            }
+        }
+    }
+    pub fn save(&self) -> io::Result<()> {
+        match Settings::path() {
+            Some(path) => self.save_to(&path),
+            None => Ok(()),
+        }
+    }
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Vector};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("tracer-settings-{}", std::process::id()))
+            .join(name)
+    }
+
+    fn camera(fov: f32) -> CameraState {
+        CameraState {
+            position: Point::new(1., 2., 3.),
+            direction: Vector::new(0., 0., -1.),
+            up: Vector::new(0., 1., 0.),
+            fov,
+        }
+    }
+
+    #[test]
+    fn bookmarks_are_kept_per_scene() {
+        let mut settings = Settings::default();
+        settings.store_bookmark("a.scene", 1, camera(1.));
+        settings.store_bookmark("b.scene", 1, camera(2.));
+        settings.store_bookmark("a.scene", 1, camera(3.));
+        assert_eq!(settings.bookmark("a.scene", 1), Some(camera(3.)));
+        assert_eq!(settings.bookmark("b.scene", 1), Some(camera(2.)));
+        assert_eq!(settings.bookmark("a.scene", 2), None);
+        assert_eq!(settings.bookmark("c.scene", 1), None);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings {
+            window_width: 1024,
+            window_position: Some((-20, 40)),
+            material_override: Some("clay".into()),
+            last_scene: Some("scenes/cornell.scene".into()),
+            tone_map: ToneMap::SoftClip { shoulder: 0.7 },
+            exposure: Exposure::Highlight {
+                min: 0.1,
+                max: 8.,
+                percentile: 0.99,
+            },
+            ..Default::default()
+        };
+        settings.store_bookmark("scenes/cornell.scene", 3, camera(1.2));
+        settings.store_bookmark("scenes/cornell.scene", 9, camera(0.4));
+        settings.store_bookmark("<demo>", 3, camera(2.));
+        let path = temp_path("round_trip.toml");
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::load_from(&path), settings);
+        settings.exposure = Exposure::Manual(2.5);
+        settings.tone_map = ToneMap::Reinhard;
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::load_from(&path), settings);
+    }
+
+    #[test]
+    fn missing_or_corrupt_files_fall_back_to_defaults() {
+        assert_eq!(
+            Settings::load_from(&temp_path("missing.toml")),
+            Settings::default()
+        );
+        let path = temp_path("corrupt.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "window_width = \"wide\"\n[[[").unwrap();
+        assert_eq!(Settings::load_from(&path), Settings::default());
+        fs::write(&path, "window_height = 300\n").unwrap();
+        let partial = Settings::load_from(&path);
+        assert_eq!(partial.window_height, 300);
+        assert_eq!(partial.window_width, Settings::default().window_width);
+        fs::write(&path, "[camera_bookmarks.1]\nfov = 1.0\n").unwrap();
+        assert_eq!(Settings::load_from(&path), Settings::default());
+    }
+}
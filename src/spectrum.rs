@@ -0,0 +1,98 @@
+use std::ops::{Add, Mul};
+
+use crate::geometry::{cie_xyz, xyz_to_rgb, Color, Vector, VISIBLE_MAX, VISIBLE_MIN};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Spectrum<const N: usize = 4> {
+    pub samples: [f32; N],
+}
+
+impl<const N: usize> Add for Spectrum<N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Spectrum {
+            samples: std::array::from_fn(|i| self.samples[i] + rhs.samples[i]),
+        }
+    }
+}
+
+impl<const N: usize> Mul for Spectrum<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Spectrum {
+            samples: std::array::from_fn(|i| self.samples[i] * rhs.samples[i]),
+        }
+    }
+}
+
+impl<const N: usize> Mul<f32> for Spectrum<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Spectrum {
+            samples: self.samples.map(|s| s * rhs),
+        }
+    }
+}
+
+impl<const N: usize> Spectrum<N> {
+    pub const ZERO: Self = Spectrum { samples: [0.; N] };
+    pub const fn new(samples: [f32; N]) -> Self {
+        Spectrum { samples }
+    }
+    pub fn constant(value: f32) -> Self {
+        Spectrum {
+            samples: [value; N],
+        }
+    }
+    pub fn wavelength(i: usize) -> f32 {
+        VISIBLE_MIN + (i as f32 + 0.5) * (VISIBLE_MAX - VISIBLE_MIN) / N as f32
+    }
+    pub fn to_xyz(&self) -> (f32, f32, f32) {
+        let (xyz, y_sum) = (0..N).fold((Vector::ZERO, 0.), |(xyz, y_sum), i| {
+            let cmf = cie_xyz(Spectrum::<N>::wavelength(i));
+            (xyz + self.samples[i] * cmf, y_sum + cmf.y)
+        });
+        let xyz = xyz / y_sum;
+        (xyz.x, xyz.y, xyz.z)
+    }
+    pub fn to_rgb(&self) -> Color {
+        let (x, y, z) = self.to_xyz();
+        xyz_to_rgb(Vector::new(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_spectrum_has_its_value_as_luminance() {
+        for value in [0., 0.5, 2.] {
+            let (_, y, _) = Spectrum::<4>::constant(value).to_xyz();
+            assert!((y - value).abs() < 1e-5, "{y} vs {value}");
+            let (_, y, _) = Spectrum::<16>::constant(value).to_xyz();
+            assert!((y - value).abs() < 1e-5, "{y} vs {value}");
+        }
+    }
+
+    #[test]
+    fn arithmetic_is_per_sample() {
+        let a = Spectrum::new([1., 2., 3., 4.]);
+        let b = Spectrum::new([0.5, 0., -1., 2.]);
+        assert_eq!((a + b).samples, [1.5, 2., 2., 6.]);
+        assert_eq!((a * b).samples, [0.5, 0., -3., 8.]);
+        assert_eq!((a * 2.).samples, [2., 4., 6., 8.]);
+    }
+
+    #[test]
+    fn flat_spectrum_is_roughly_neutral() {
+        let rgb = Spectrum::<16>::constant(1.).to_rgb();
+        // Equal-energy white sits a little red of sRGB's D65 white point.
+        for channel in [rgb.x, rgb.y, rgb.z] {
+            assert!((channel - 1.).abs() < 0.25, "{rgb:?}");
+        }
+    }
+}
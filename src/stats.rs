@@ -0,0 +1,168 @@
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Serialize, Serializer};
+#[cfg(feature = "stats")]
+use std::{sync::Mutex, time::Instant};
+
+pub static RAY_COUNT: AtomicU64 = AtomicU64::new(0);
+pub static HIT_COUNT: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static INTERSECTION_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn count_intersection_test() {
+    INTERSECTION_TESTS.with(|tests| tests.set(tests.get() + 1));
+}
+
+pub fn take_intersection_tests() -> u64 {
+    INTERSECTION_TESTS.with(|tests| tests.replace(0))
+}
+
+pub fn get_ray_count() -> u64 {
+    RAY_COUNT.load(Ordering::Relaxed)
+}
+
+pub fn get_hit_count() -> u64 {
+    HIT_COUNT.load(Ordering::Relaxed)
+}
+
+pub fn reset_ray_stats() {
+    RAY_COUNT.store(0, Ordering::Relaxed);
+    HIT_COUNT.store(0, Ordering::Relaxed);
+}
+
+/// Shape families broken out in the per-type intersection counters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeType {
+    Sphere,
+    Triangle,
+    Curve,
+}
+
+impl ShapeType {
+    pub const ALL: [ShapeType; 3] = [ShapeType::Sphere, ShapeType::Triangle, ShapeType::Curve];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShapeType::Sphere => "sphere",
+            ShapeType::Triangle => "triangle",
+            ShapeType::Curve => "curve",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ShapeCounter {
+    pub tests: u64,
+    pub hits: u64,
+    /// Estimated from every `TIMING_INTERVAL`th test.
+    #[serde(serialize_with = "seconds")]
+    pub time: Duration,
+}
+
+/// Counters indexed by `ShapeType as usize`.
+pub type ShapeStats = [ShapeCounter; 3];
+
+pub(crate) fn seconds<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+/// Writes shape counters as a map keyed by shape name.
+pub(crate) fn shape_stats<S: Serializer>(
+    shapes: &Option<ShapeStats>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match shapes {
+        Some(shapes) => s.collect_map(
+            ShapeType::ALL
+                .iter()
+                .map(|&shape| (shape.name(), shapes[shape as usize])),
+        ),
+        None => s.serialize_none(),
+    }
+}
+
+/// Only one test in this many is timed, and its time is scaled up, so the clock reads stay off
+/// the hot path.
+#[cfg(feature = "stats")]
+pub const TIMING_INTERVAL: u64 = 64;
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static SHAPE_COUNTERS: Cell<ShapeStats> = const {
+        Cell::new(
+            [ShapeCounter {
+                tests: 0,
+                hits: 0,
+                time: Duration::ZERO,
+            }; 3],
+        )
+    };
+}
+
+#[cfg(feature = "stats")]
+static SHAPE_TOTALS: Mutex<ShapeStats> = Mutex::new(
+    [ShapeCounter {
+        tests: 0,
+        hits: 0,
+        time: Duration::ZERO,
+    }; 3],
+);
+
+/// Runs one primitive intersection test, counting it against `shape` when the `stats` feature
+/// is on and doing nothing extra otherwise.
+#[cfg(feature = "stats")]
+pub fn record_shape_test<T>(shape: ShapeType, test: impl FnOnce() -> Option<T>) -> Option<T> {
+    let timed = SHAPE_COUNTERS.with(|counters| {
+        counters.get()[shape as usize]
+            .tests
+            .is_multiple_of(TIMING_INTERVAL)
+    });
+    let start = timed.then(Instant::now);
+    let result = test();
+    let time = start.map_or(Duration::ZERO, |start| {
+        start.elapsed() * TIMING_INTERVAL as u32
+    });
+    SHAPE_COUNTERS.with(|counters| {
+        let mut all = counters.get();
+        let counter = &mut all[shape as usize];
+        counter.tests += 1;
+        counter.hits += result.is_some() as u64;
+        counter.time += time;
+        counters.set(all);
+    });
+    result
+}
+
+#[cfg(not(feature = "stats"))]
+#[inline(always)]
+pub fn record_shape_test<T>(_shape: ShapeType, test: impl FnOnce() -> Option<T>) -> Option<T> {
+    test()
+}
+
+/// Moves this thread's shape counters into the shared totals.
+#[cfg(feature = "stats")]
+pub fn flush_shape_counters() {
+    let local = SHAPE_COUNTERS.with(|counters| counters.replace(ShapeStats::default()));
+    let mut totals = SHAPE_TOTALS.lock().unwrap_or_else(|e| e.into_inner());
+    for (total, local) in totals.iter_mut().zip(local) {
+        total.tests += local.tests;
+        total.hits += local.hits;
+        total.time += local.time;
+    }
+}
+
+#[cfg(feature = "stats")]
+pub fn discard_shape_counters() {
+    SHAPE_COUNTERS.with(|counters| counters.set(ShapeStats::default()));
+}
+
+#[cfg(feature = "stats")]
+pub fn take_shape_stats() -> ShapeStats {
+    std::mem::take(&mut *SHAPE_TOTALS.lock().unwrap_or_else(|e| e.into_inner()))
+}
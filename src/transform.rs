@@ -0,0 +1,185 @@
+use std::{ops::Mul, sync::Arc};
+
+use crate::{
+    content_hash::ContentHasher,
+    geometry::{Aabb, Hit, Hittable, Interval, Point, Ray, Vector},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Mat4 {
+            m: std::array::from_fn(|i| {
+                std::array::from_fn(|j| (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum())
+            }),
+        }
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Mat4 {
+        m: [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ],
+    };
+    pub fn translation(offset: Vector) -> Self {
+        let mut t = Mat4::IDENTITY;
+        t.m[0][3] = offset.x;
+        t.m[1][3] = offset.y;
+        t.m[2][3] = offset.z;
+        t
+    }
+    pub fn scale(factor: Vector) -> Self {
+        let mut t = Mat4::IDENTITY;
+        t.m[0][0] = factor.x;
+        t.m[1][1] = factor.y;
+        t.m[2][2] = factor.z;
+        t
+    }
+    pub fn rotation(axis: Vector, angle: f32) -> Self {
+        let a = axis.normalize();
+        let (s, c) = angle.sin_cos();
+        let t = 1. - c;
+        Mat4 {
+            m: [
+                [
+                    t * a.x * a.x + c,
+                    t * a.x * a.y - s * a.z,
+                    t * a.x * a.z + s * a.y,
+                    0.,
+                ],
+                [
+                    t * a.x * a.y + s * a.z,
+                    t * a.y * a.y + c,
+                    t * a.y * a.z - s * a.x,
+                    0.,
+                ],
+                [
+                    t * a.x * a.z - s * a.y,
+                    t * a.y * a.z + s * a.x,
+                    t * a.z * a.z + c,
+                    0.,
+                ],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+    pub fn transpose(&self) -> Self {
+        Mat4 {
+            m: std::array::from_fn(|i| std::array::from_fn(|j| self.m[j][i])),
+        }
+    }
+    pub fn transform_point(&self, p: Point) -> Point {
+        self.transform_vector(p) + Vector::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+    pub fn transform_vector(&self, v: Vector) -> Vector {
+        let m = &self.m;
+        Vector::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+    pub fn inverse_affine(&self) -> Self {
+        let m = &self.m;
+        let c0 = Vector::new(m[0][0], m[1][0], m[2][0]);
+        let c1 = Vector::new(m[0][1], m[1][1], m[2][1]);
+        let c2 = Vector::new(m[0][2], m[1][2], m[2][2]);
+        let det = c0.dot(c1.cross(c2));
+        let r0 = c1.cross(c2) / det;
+        let r1 = c2.cross(c0) / det;
+        let r2 = c0.cross(c1) / det;
+        let t = Vector::new(m[0][3], m[1][3], m[2][3]);
+        Mat4 {
+            m: [
+                [r0.x, r0.y, r0.z, -r0.dot(t)],
+                [r1.x, r1.y, r1.z, -r1.dot(t)],
+                [r2.x, r2.y, r2.z, -r2.dot(t)],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+}
+
+pub struct Transformed {
+    shape: Arc<dyn Hittable + Sync + Send>,
+    transform: Mat4,
+    inverse: Mat4,
+}
+
+impl Transformed {
+    pub fn new(shape: Arc<dyn Hittable + Sync + Send>, transform: Mat4) -> Self {
+        Transformed {
+            shape,
+            transform,
+            inverse: transform.inverse_affine(),
+        }
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<Hit> {
+        let direction = self.inverse.transform_vector(ray.direction);
+        let scale = direction.length();
+        let local = Ray {
+            origin: self.inverse.transform_point(ray.origin),
+            direction: direction / scale,
+            wavelength: ray.wavelength,
+            time: ray.time,
+        };
+        let hit = self.shape.hit(
+            &local,
+            &Interval::new(interval.min * scale, interval.max * scale),
+        )?;
+        Some(Hit {
+            t: hit.t / scale,
+            normal: self
+                .inverse
+                .transpose()
+                .transform_vector(hit.normal)
+                .normalize(),
+            is_front: hit.is_front,
+            object_id: hit.object_id,
+            depth: hit.depth,
+            outside_ior: hit.outside_ior,
+        })
+    }
+    fn contains(&self, p: Point) -> bool {
+        self.shape.contains(self.inverse.transform_point(p))
+    }
+    fn uv(&self, p: Point) -> (f32, f32) {
+        self.shape.uv(self.inverse.transform_point(p))
+    }
+    fn velocity_at(&self, p: Point) -> Vector {
+        self.transform
+            .transform_vector(self.shape.velocity_at(self.inverse.transform_point(p)))
+    }
+    fn bounding_box(&self) -> Aabb {
+        let b = self.shape.bounding_box();
+        (0..8).fold(Aabb::EMPTY, |bounds, i| {
+            let corner = Point::new(
+                if i & 1 == 0 { b.min.x } else { b.max.x },
+                if i & 2 == 0 { b.min.y } else { b.max.y },
+                if i & 4 == 0 { b.min.z } else { b.max.z },
+            );
+            let p = self.transform.transform_point(corner);
+            bounds.union(&Aabb::new(p, p))
+        })
+    }
+    fn hash_content(&self, state: &mut ContentHasher) {
+        state.write_str("transformed");
+        for row in self.transform.m {
+            row.into_iter().for_each(|x| state.write_f32(x));
+        }
+        self.shape.hash_content(state);
+    }
+}